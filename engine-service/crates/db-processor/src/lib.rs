@@ -1,10 +1,12 @@
 pub mod query;
+pub mod settlement;
 pub mod types;
 
 use fred::prelude::RedisValue;
-use query::{insert_order, insert_trade};
-use reqwest::Client;
+use query::{insert_balance_change, insert_order, insert_trade, upsert_asset, upsert_market};
+use reqwest::{Client, RequestBuilder};
 use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde_json::from_str;
 use sqlx::{Pool, Postgres};
 use types::DatabaseRequests;
@@ -28,20 +30,34 @@ pub async fn handle_db_updates(data: Vec<RedisValue>, pg_pool: &Pool<Postgres>)
         Ok(db_data) => match db_data {
             DatabaseRequests::InsertTrade(db_trade) => {
                 println!("Received Trade {:?}", db_trade);
-                match insert_trade(pg_pool, db_trade.clone()).await {
-                    Ok(_) => {
-                        // Trade inserted successfully, now update balances
-                        if let Err(e) = update_balances_after_trade(&db_trade).await {
-                            println!("Failed to update balances after trade: {:?}", e);
-                        }
-                    }
-                    Err(e) => println!("Failed to insert trade: {:?}", e),
+                // its settlement legs go into the outbox with it - the settlement worker applies them
+                if let Err(e) = insert_trade(pg_pool, db_trade).await {
+                    println!("Failed to insert trade: {:?}", e);
                 }
             }
             DatabaseRequests::InsertOrder(db_data) => {
                 println!("Received Order {:?}", db_data);
                 let _ = insert_order(pg_pool, db_data).await;
             }
+            DatabaseRequests::UpsertMarket(db_market) => {
+                println!("Received Market {:?}", db_market);
+                if let Err(e) = upsert_market(pg_pool, db_market).await {
+                    println!("Failed to upsert market: {:?}", e);
+                }
+            }
+            DatabaseRequests::UpsertAsset(db_asset) => {
+                println!("Received Asset {:?}", db_asset);
+                if let Err(e) = upsert_asset(pg_pool, db_asset).await {
+                    println!("Failed to upsert asset: {:?}", e);
+                }
+            }
+            DatabaseRequests::UpdateBalance(db_balance) => {
+                println!("Received Balance {:?}", db_balance);
+                // the legs that replay it on the user service go into the outbox with it
+                if let Err(e) = insert_balance_change(pg_pool, db_balance).await {
+                    println!("Failed to upsert balance: {:?}", e);
+                }
+            }
         },
         Err(err) => {
             println!("Failed to deserialize db request: {:?}", err);
@@ -49,79 +65,70 @@ pub async fn handle_db_updates(data: Vec<RedisValue>, pg_pool: &Pool<Postgres>)
     }
 }
 
-async fn update_balances_after_trade(trade: &types::DbTrade) -> Result<(), Box<dyn std::error::Error>> {
-    let client = Client::new();
+// every balance the user service holds, for an engine starting on an empty ledger to import
+pub async fn get_user_service_balances() -> Result<Vec<types::DbBalance>, Box<dyn std::error::Error>> {
     let user_service_url = std::env::var("USER_SERVICE_URL").unwrap_or_else(|_| "http://localhost:3001".to_string());
 
-    let quantity = trade.quantity.to_f64().ok_or("Invalid quantity")?;
-    let total_price = (trade.price * trade.quantity).to_f64().ok_or("Invalid price")?;
-
-    match trade.order_side.as_str() {
-        "BUY" => {
-            // Update buyer's balances (current user) - unlock quote asset and add base asset
-            unlock_funds(&client, &user_service_url, &trade.user_id, &trade.quote_asset, total_price).await?;
-            update_balance(&client, &user_service_url, &trade.user_id, &trade.base_asset, quantity, "add").await?;
-
-            // Update seller's balances (other user) - unlock base asset and add quote asset
-            unlock_funds(&client, &user_service_url, &trade.other_user_id, &trade.base_asset, quantity).await?;
-            update_balance(&client, &user_service_url, &trade.other_user_id, &trade.quote_asset, total_price, "add").await?;
-        }
-        "SELL" => {
-            // Update seller's balances (current user) - unlock base asset and add quote asset
-            unlock_funds(&client, &user_service_url, &trade.user_id, &trade.base_asset, quantity).await?;
-            update_balance(&client, &user_service_url, &trade.user_id, &trade.quote_asset, total_price, "add").await?;
-
-            // Update buyer's balances (other user) - unlock quote asset and add base asset
-            unlock_funds(&client, &user_service_url, &trade.other_user_id, &trade.quote_asset, total_price).await?;
-            update_balance(&client, &user_service_url, &trade.other_user_id, &trade.base_asset, quantity, "add").await?;
-        }
-        _ => return Err("Invalid order side".into()),
+    let response = Client::new()
+        .get(format!("{}/api/balance/export", user_service_url))
+        .header("x-service-key", "engine-service-key")
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        return Err(format!("Failed to export balances: {}", response.status()).into());
     }
 
-    Ok(())
+    let body: serde_json::Value = response.json().await?;
+    let balances: Vec<types::UserServiceBalance> = serde_json::from_value(body["data"]["balances"].clone())?;
+    let timestamp = chrono::Utc::now().timestamp_millis();
+
+    Ok(balances
+        .into_iter()
+        .map(|balance| types::DbBalance {
+            user_id: balance.user_id,
+            asset: balance.currency,
+            available: balance.balance - balance.locked_balance,
+            locked: balance.locked_balance,
+            timestamp,
+            change_id: String::new(),
+            settlement_legs: vec![],
+        })
+        .collect())
 }
 
-async fn unlock_funds(
+// a call to one of the user service's balance endpoints - update, lock or unlock
+fn balance_request(
     client: &Client,
     base_url: &str,
+    path: &str,
     user_id: &str,
     asset: &str,
-    amount: f64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("{}/api/balance/unlock-funds", base_url);
-    let body = serde_json::json!({
-        "userId": user_id,
-        "asset": asset,
-        "amount": amount
+    amount: Decimal,
+    operation: Option<&str>,
+) -> Result<RequestBuilder, Box<dyn std::error::Error>> {
+    let url = format!("{}/api/balance/{}", base_url, path);
+    let mut body = serde_json::json!({
+        "currency": asset,
+        "amount": amount.to_f64().ok_or("Invalid amount")?,
     });
-
-    let response = client.post(&url).json(&body).send().await?;
-    if !response.status().is_success() {
-        return Err(format!("Failed to unlock funds: {}", response.status()).into());
+    if let Some(operation) = operation {
+        body["operation"] = serde_json::json!(operation);
     }
 
-    Ok(())
+    Ok(client
+        .post(&url)
+        .header("x-service-key", "engine-service-key")
+        .header("x-user-id", user_id)
+        .json(&body))
 }
 
-async fn update_balance(
-    client: &Client,
-    base_url: &str,
-    user_id: &str,
-    asset: &str,
-    amount: f64,
-    operation: &str,
+async fn send_balance_request(
+    request: RequestBuilder,
+    path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let url = format!("{}/api/balance/update", base_url);
-    let body = serde_json::json!({
-        "userId": user_id,
-        "asset": asset,
-        "amount": amount,
-        "operation": operation
-    });
-
-    let response = client.post(&url).json(&body).send().await?;
+    let response = request.send().await?;
     if !response.status().is_success() {
-        return Err(format!("Failed to update balance: {}", response.status()).into());
+        return Err(format!("Failed to {} balance: {}", path, response.status()).into());
     }
 
     Ok(())
@@ -1,4 +1,5 @@
 use db_processor::handle_db_updates;
+use db_processor::settlement::run_settlement_worker;
 use redis::{RedisManager, RedisQueues};
 use sqlx_postgres::PostgresDb;
 pub mod query;
@@ -17,6 +18,9 @@ async fn main() {
     let pg_pool = postgres.get_pg_connection().unwrap();
     println!("Postgres connection pool ready!");
 
+    // settlement legs of stored trades are applied to the user service in the background
+    tokio::spawn(run_settlement_worker(pg_pool.clone()));
+
     // // update DATABASE_URL in sqlx_postgres to run the script
     // if let Err(e) = generate_random_trades(&pg_pool, 100000).await {
     //     println!("Error generating trades: {:?}", e);
@@ -1,36 +1,119 @@
-use crate::types::{DbOrder, DbTrade, KlineData, TickerData};
+use crate::types::{
+    DbAsset, DbBalance, DbMarket, DbOrder, DbOutboxLeg, DbSettlementLeg, DbTrade, DbTradeVolume,
+    KlineData, TickerData,
+};
 use chrono::{DateTime, Duration, Utc};
 use rust_decimal::Decimal;
 use sqlx::{Pool, Postgres};
 
+// the trade and the settlement legs it moved are written in one transaction, so a stored trade is always
+// settled and a replayed one never adds its legs twice
 pub async fn insert_trade(pool: &Pool<Postgres>, trade: DbTrade) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
     sqlx::query(
         "INSERT INTO trades(
-          trade_id, market, price, quantity, user_id, other_user_id, order_id, timestamp
-      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+          trade_id, market, price, quantity, user_id, other_user_id, order_id, timestamp,
+          taker_fee, taker_fee_asset, maker_fee, maker_fee_asset
+      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
     )
     .bind(trade.trade_id)
-    .bind(trade.market)
+    .bind(&trade.market)
     .bind(trade.price)
     .bind(trade.quantity)
     .bind(trade.user_id)
     .bind(trade.other_user_id)
     .bind(trade.order_id)
     .bind(trade.timestamp)
-    .execute(pool)
+    .bind(trade.taker_fee)
+    .bind(trade.taker_fee_asset)
+    .bind(trade.maker_fee)
+    .bind(trade.maker_fee_asset)
+    .execute(&mut *transaction)
+    .await?;
+
+    // trade ids only count up within a market
+    let settlement_id = format!("{}:{}", trade.market, trade.trade_id);
+    insert_settlement_legs(&mut transaction, &settlement_id, trade.settlement_legs).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+// a balance change and the legs that replay it on the user service are written in one transaction, like a
+// trade's. the legs are keyed by the change's id, so a change that is processed twice is only queued once
+pub async fn insert_balance_change(
+    pool: &Pool<Postgres>,
+    balance: DbBalance,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO balances(user_id, asset, available, locked, updated_at) VALUES ($1, $2, $3, $4, $5)
+      ON CONFLICT (user_id, asset) DO UPDATE SET
+        available = EXCLUDED.available,
+        locked = EXCLUDED.locked,
+        updated_at = EXCLUDED.updated_at",
+    )
+    .bind(&balance.user_id)
+    .bind(&balance.asset)
+    .bind(balance.available)
+    .bind(balance.locked)
+    .bind(balance.timestamp)
+    .execute(&mut *transaction)
     .await?;
 
+    let settlement_id = format!("balance:{}", balance.change_id);
+    insert_settlement_legs(&mut transaction, &settlement_id, balance.settlement_legs).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn insert_settlement_legs(
+    transaction: &mut sqlx::Transaction<'_, Postgres>,
+    settlement_id: &str,
+    legs: Vec<DbSettlementLeg>,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis();
+    for leg in legs {
+        sqlx::query(
+            "INSERT INTO settlement_outbox(
+              settlement_id, leg, seq, user_id, asset, amount, operation, next_attempt_at, created_at, updated_at
+          ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8, $8)
+          ON CONFLICT (settlement_id, leg) DO NOTHING",
+        )
+        .bind(settlement_id)
+        .bind(leg.leg)
+        .bind(leg.seq)
+        .bind(leg.user_id)
+        .bind(leg.asset)
+        .bind(leg.amount)
+        .bind(leg.operation)
+        .bind(now)
+        .execute(&mut **transaction)
+        .await?;
+    }
+
     Ok(())
 }
 
 pub async fn insert_order(pool: &Pool<Postgres>, order: DbOrder) -> Result<(), sqlx::Error> {
     sqlx::query(
         "INSERT INTO orders(
-          order_id, market, price, quantity, filled_quantity, user_id, side, order_type, order_status, timestamp
-      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+          order_id, market, price, quantity, filled_quantity, user_id, side, order_type, order_status, timestamp,
+          time_in_force, expires_at, trigger_price, trailing_offset, trailing_percent,
+          oco_group_id, display_quantity
+      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
       ON CONFLICT (order_id) DO UPDATE SET
+        price = EXCLUDED.price,
+        quantity = EXCLUDED.quantity,
+        trigger_price = EXCLUDED.trigger_price,
         filled_quantity = EXCLUDED.filled_quantity,
-        order_status = EXCLUDED.order_status",
+        order_status = EXCLUDED.order_status,
+        order_type = EXCLUDED.order_type",
     )
     .bind(order.order_id)
     .bind(order.market)
@@ -42,12 +125,191 @@ pub async fn insert_order(pool: &Pool<Postgres>, order: DbOrder) -> Result<(), s
     .bind(order.order_type)
     .bind(order.order_status)
     .bind(order.timestamp)
+    .bind(order.time_in_force)
+    .bind(order.expires_at)
+    .bind(order.trigger_price)
+    .bind(order.trailing_offset)
+    .bind(order.trailing_percent)
+    .bind(order.oco_group_id)
+    .bind(order.display_quantity)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn upsert_market(pool: &Pool<Postgres>, market: DbMarket) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO markets(
+          symbol, base_asset, quote_asset, min_order_size, max_order_size, price_precision,
+          quantity_precision, status, trading_state, price_band_percent, reference_price,
+          circuit_breaker_percent, circuit_breaker_window_secs, circuit_breaker_halt_secs,
+          matching_policy, maker_fee_rate, taker_fee_rate
+      ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+      ON CONFLICT (symbol) DO UPDATE SET
+        min_order_size = EXCLUDED.min_order_size,
+        max_order_size = EXCLUDED.max_order_size,
+        price_precision = EXCLUDED.price_precision,
+        quantity_precision = EXCLUDED.quantity_precision,
+        status = EXCLUDED.status,
+        trading_state = EXCLUDED.trading_state,
+        price_band_percent = EXCLUDED.price_band_percent,
+        reference_price = EXCLUDED.reference_price,
+        circuit_breaker_percent = EXCLUDED.circuit_breaker_percent,
+        circuit_breaker_window_secs = EXCLUDED.circuit_breaker_window_secs,
+        circuit_breaker_halt_secs = EXCLUDED.circuit_breaker_halt_secs,
+        matching_policy = EXCLUDED.matching_policy,
+        maker_fee_rate = EXCLUDED.maker_fee_rate,
+        taker_fee_rate = EXCLUDED.taker_fee_rate",
+    )
+    .bind(market.symbol)
+    .bind(market.base_asset)
+    .bind(market.quote_asset)
+    .bind(market.min_order_size)
+    .bind(market.max_order_size)
+    .bind(market.price_precision)
+    .bind(market.quantity_precision)
+    .bind(market.status)
+    .bind(market.trading_state)
+    .bind(market.price_band_percent)
+    .bind(market.reference_price)
+    .bind(market.circuit_breaker_percent)
+    .bind(market.circuit_breaker_window_secs)
+    .bind(market.circuit_breaker_halt_secs)
+    .bind(market.matching_policy)
+    .bind(market.maker_fee_rate)
+    .bind(market.taker_fee_rate)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// adds markets that are not in the registry yet - markets already there keep their admin changes
+pub async fn seed_markets(pool: &Pool<Postgres>, markets: Vec<DbMarket>) -> Result<(), sqlx::Error> {
+    for market in markets {
+        sqlx::query(
+            "INSERT INTO markets(
+              symbol, base_asset, quote_asset, min_order_size, max_order_size, price_precision,
+              quantity_precision, status, trading_state, price_band_percent, reference_price,
+              circuit_breaker_percent, circuit_breaker_window_secs, circuit_breaker_halt_secs,
+              matching_policy, maker_fee_rate, taker_fee_rate
+          ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+          ON CONFLICT (symbol) DO NOTHING",
+        )
+        .bind(market.symbol)
+        .bind(market.base_asset)
+        .bind(market.quote_asset)
+        .bind(market.min_order_size)
+        .bind(market.max_order_size)
+        .bind(market.price_precision)
+        .bind(market.quantity_precision)
+        .bind(market.status)
+        .bind(market.trading_state)
+        .bind(market.price_band_percent)
+        .bind(market.reference_price)
+        .bind(market.circuit_breaker_percent)
+        .bind(market.circuit_breaker_window_secs)
+        .bind(market.circuit_breaker_halt_secs)
+        .bind(market.matching_policy)
+        .bind(market.maker_fee_rate)
+        .bind(market.taker_fee_rate)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn get_markets_from_db(pool: &Pool<Postgres>) -> Result<Vec<DbMarket>, sqlx::Error> {
+    let markets = sqlx::query!("SELECT * FROM markets ORDER BY symbol asc")
+        .fetch_all(pool)
+        .await?;
+
+    let markets_vec: Vec<DbMarket> = markets
+        .iter()
+        .map(|market| DbMarket {
+            symbol: market.symbol.clone(),
+            base_asset: market.base_asset.clone(),
+            quote_asset: market.quote_asset.clone(),
+            min_order_size: market.min_order_size.to_string().parse::<Decimal>().unwrap(),
+            max_order_size: market.max_order_size.to_string().parse::<Decimal>().unwrap(),
+            price_precision: market.price_precision,
+            quantity_precision: market.quantity_precision,
+            status: market.status.clone(),
+            trading_state: market.trading_state.clone(),
+            price_band_percent: market
+                .price_band_percent
+                .as_ref()
+                .map(|price_band_percent| price_band_percent.to_string().parse::<Decimal>().unwrap()),
+            reference_price: market
+                .reference_price
+                .as_ref()
+                .map(|reference_price| reference_price.to_string().parse::<Decimal>().unwrap()),
+            circuit_breaker_percent: market.circuit_breaker_percent.as_ref().map(
+                |circuit_breaker_percent| circuit_breaker_percent.to_string().parse::<Decimal>().unwrap(),
+            ),
+            circuit_breaker_window_secs: market.circuit_breaker_window_secs,
+            circuit_breaker_halt_secs: market.circuit_breaker_halt_secs,
+            matching_policy: market.matching_policy.clone(),
+            maker_fee_rate: market.maker_fee_rate.to_string().parse::<Decimal>().unwrap(),
+            taker_fee_rate: market.taker_fee_rate.to_string().parse::<Decimal>().unwrap(),
+        })
+        .collect();
+
+    Ok(markets_vec)
+}
+
+pub async fn upsert_asset(pool: &Pool<Postgres>, asset: DbAsset) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO assets(symbol, decimals, status) VALUES ($1, $2, $3)
+      ON CONFLICT (symbol) DO UPDATE SET
+        decimals = EXCLUDED.decimals,
+        status = EXCLUDED.status",
+    )
+    .bind(asset.symbol)
+    .bind(asset.decimals)
+    .bind(asset.status)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
+// adds assets that are not in the registry yet - assets already there keep their admin changes
+pub async fn seed_assets(pool: &Pool<Postgres>, assets: Vec<DbAsset>) -> Result<(), sqlx::Error> {
+    for asset in assets {
+        sqlx::query(
+            "INSERT INTO assets(symbol, decimals, status) VALUES ($1, $2, $3)
+          ON CONFLICT (symbol) DO NOTHING",
+        )
+        .bind(asset.symbol)
+        .bind(asset.decimals)
+        .bind(asset.status)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn get_assets_from_db(pool: &Pool<Postgres>) -> Result<Vec<DbAsset>, sqlx::Error> {
+    let assets = sqlx::query!("SELECT * FROM assets ORDER BY symbol asc")
+        .fetch_all(pool)
+        .await?;
+
+    let assets_vec: Vec<DbAsset> = assets
+        .iter()
+        .map(|asset| DbAsset {
+            symbol: asset.symbol.clone(),
+            decimals: asset.decimals,
+            status: asset.status.clone(),
+        })
+        .collect();
+
+    Ok(assets_vec)
+}
+
 pub async fn get_trades_from_db(
     pool: &Pool<Postgres>,
     market: String,
@@ -73,6 +335,11 @@ pub async fn get_trades_from_db(
             order_side: "".to_string(),
             base_asset: "".to_string(),
             quote_asset: "".to_string(),
+            taker_fee: trade.taker_fee.to_string().parse::<Decimal>().unwrap(),
+            taker_fee_asset: trade.taker_fee_asset.clone(),
+            maker_fee: trade.maker_fee.to_string().parse::<Decimal>().unwrap(),
+            maker_fee_asset: trade.maker_fee_asset.clone(),
+            settlement_legs: vec![],
         })
         .collect();
 
@@ -243,14 +510,13 @@ pub async fn get_tickers_from_db(pool: &Pool<Postgres>) -> Result<Vec<TickerData
         })
         .collect();
 
-    // If no tickers from trades, return default configured markets
+    // If no tickers from trades, return the listed markets from the registry
     if ticker_data.is_empty() {
-        let configured_markets = vec![
-            "SOL_USDC",
-            "BTC_USDC",
-            "ETH_USDC",
-            "SOL_USDT"
-        ];
+        let configured_markets = get_markets_from_db(pool)
+            .await?
+            .into_iter()
+            .filter(|market| market.status != "DELISTED")
+            .map(|market| market.symbol);
 
         for market in configured_markets {
             ticker_data.push(TickerData {
@@ -290,6 +556,20 @@ pub async fn get_latest_trade_id_from_db(
     Ok(trade_id)
 }
 
+pub async fn get_latest_trade_price_from_db(
+    pool: &Pool<Postgres>,
+    market: String,
+) -> Result<Option<Decimal>, sqlx::Error> {
+    let latest_trade = sqlx::query!(
+        "SELECT price FROM trades WHERE market = $1 ORDER BY trade_id desc LIMIT 1",
+        market
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(latest_trade.map(|record| record.price.to_string().parse::<Decimal>().unwrap()))
+}
+
 pub async fn get_orders_from_db(
     pool: &Pool<Postgres>,
     market: String,
@@ -314,8 +594,245 @@ pub async fn get_orders_from_db(
             order_type: order.order_type.clone(),
             order_status: order.order_status.clone(),
             timestamp: order.timestamp,
+            time_in_force: order.time_in_force.clone(),
+            expires_at: order.expires_at,
+            trigger_price: order
+                .trigger_price
+                .as_ref()
+                .map(|trigger_price| trigger_price.to_string().parse::<Decimal>().unwrap()),
+            trailing_offset: order
+                .trailing_offset
+                .as_ref()
+                .map(|trailing_offset| trailing_offset.to_string().parse::<Decimal>().unwrap()),
+            trailing_percent: order
+                .trailing_percent
+                .as_ref()
+                .map(|trailing_percent| trailing_percent.to_string().parse::<Decimal>().unwrap()),
+            oco_group_id: order.oco_group_id.clone(),
+            display_quantity: order
+                .display_quantity
+                .as_ref()
+                .map(|display_quantity| display_quantity.to_string().parse::<Decimal>().unwrap()),
         })
         .collect();
 
     Ok(orders_vec)
 }
+
+// whether balances from `source` were already taken into the ledger
+pub async fn is_ledger_imported(pool: &Pool<Postgres>, source: &str) -> Result<bool, sqlx::Error> {
+    let import = sqlx::query!("SELECT source FROM ledger_imports WHERE source = $1", source)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(import.is_some())
+}
+
+// the imported balances and the marker recording the import are written in one transaction, so an
+// interrupted import runs again on the next start and a finished one never does
+pub async fn import_balances(
+    pool: &Pool<Postgres>,
+    source: &str,
+    balances: Vec<DbBalance>,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let count = balances.len() as i32;
+
+    for balance in balances {
+        sqlx::query(
+            "INSERT INTO balances(user_id, asset, available, locked, updated_at) VALUES ($1, $2, $3, $4, $5)
+          ON CONFLICT (user_id, asset) DO UPDATE SET
+            available = EXCLUDED.available,
+            locked = EXCLUDED.locked,
+            updated_at = EXCLUDED.updated_at",
+        )
+        .bind(balance.user_id)
+        .bind(balance.asset)
+        .bind(balance.available)
+        .bind(balance.locked)
+        .bind(balance.timestamp)
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    sqlx::query("INSERT INTO ledger_imports(source, balances, imported_at) VALUES ($1, $2, $3)")
+        .bind(source)
+        .bind(count)
+        .bind(chrono::Utc::now().timestamp_millis())
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+pub async fn get_balances_from_db(pool: &Pool<Postgres>) -> Result<Vec<DbBalance>, sqlx::Error> {
+    let balances = sqlx::query!("SELECT * FROM balances ORDER BY user_id asc, asset asc")
+        .fetch_all(pool)
+        .await?;
+
+    let balances_vec: Vec<DbBalance> = balances
+        .iter()
+        .map(|balance| DbBalance {
+            user_id: balance.user_id.clone(),
+            asset: balance.asset.clone(),
+            available: balance.available.to_string().parse::<Decimal>().unwrap(),
+            locked: balance.locked.to_string().parse::<Decimal>().unwrap(),
+            timestamp: balance.updated_at,
+            change_id: String::new(),
+            settlement_legs: vec![],
+        })
+        .collect();
+
+    Ok(balances_vec)
+}
+
+// quote volume each user traded per day since `since`, on either side of the trade
+pub async fn get_trade_volumes_from_db(
+    pool: &Pool<Postgres>,
+    since: i64,
+) -> Result<Vec<DbTradeVolume>, sqlx::Error> {
+    let volumes = sqlx::query!(
+        r#"SELECT user_id AS "user_id!", day AS "day!", SUM(volume) AS "volume!" FROM (
+            SELECT user_id, timestamp / 86400000 AS day, price * quantity AS volume FROM trades WHERE timestamp >= $1
+            UNION ALL
+            SELECT other_user_id, timestamp / 86400000, price * quantity FROM trades WHERE timestamp >= $1
+        ) AS user_trades GROUP BY user_id, day"#,
+        since
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let volumes_vec: Vec<DbTradeVolume> = volumes
+        .iter()
+        .map(|volume| DbTradeVolume {
+            user_id: volume.user_id.clone(),
+            timestamp: volume.day * 86400000,
+            volume: volume.volume.to_string().parse::<Decimal>().unwrap(),
+        })
+        .collect();
+
+    Ok(volumes_vec)
+}
+
+// pending legs that are due, in the order they were queued. a leg waits while an earlier leg of its
+// settlement is backing off or has failed
+pub async fn get_due_settlement_legs(
+    pool: &Pool<Postgres>,
+    now: i64,
+    limit: i64,
+) -> Result<Vec<DbOutboxLeg>, sqlx::Error> {
+    let legs = sqlx::query!(
+        r#"SELECT * FROM settlement_outbox AS outbox
+        WHERE status = 'PENDING' AND next_attempt_at <= $1
+        AND NOT EXISTS (
+            SELECT 1 FROM settlement_outbox AS earlier
+            WHERE earlier.settlement_id = outbox.settlement_id AND earlier.seq < outbox.seq
+            AND (earlier.status = 'FAILED' OR (earlier.status = 'PENDING' AND earlier.next_attempt_at > $1))
+        )
+        ORDER BY id asc
+        LIMIT $2"#,
+        now,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let legs_vec: Vec<DbOutboxLeg> = legs
+        .iter()
+        .map(|leg| DbOutboxLeg {
+            settlement_id: leg.settlement_id.clone(),
+            leg: leg.leg.clone(),
+            seq: leg.seq,
+            user_id: leg.user_id.clone(),
+            asset: leg.asset.clone(),
+            amount: leg.amount.to_string().parse::<Decimal>().unwrap(),
+            operation: leg.operation.clone(),
+            status: leg.status.clone(),
+            attempts: leg.attempts,
+            next_attempt_at: leg.next_attempt_at,
+            last_error: leg.last_error.clone(),
+            created_at: leg.created_at,
+            updated_at: leg.updated_at,
+        })
+        .collect();
+
+    Ok(legs_vec)
+}
+
+pub async fn get_settlement_legs_from_db(
+    pool: &Pool<Postgres>,
+    status: String,
+) -> Result<Vec<DbOutboxLeg>, sqlx::Error> {
+    let legs = sqlx::query!(
+        "SELECT * FROM settlement_outbox WHERE status = $1 ORDER BY id asc",
+        status
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let legs_vec: Vec<DbOutboxLeg> = legs
+        .iter()
+        .map(|leg| DbOutboxLeg {
+            settlement_id: leg.settlement_id.clone(),
+            leg: leg.leg.clone(),
+            seq: leg.seq,
+            user_id: leg.user_id.clone(),
+            asset: leg.asset.clone(),
+            amount: leg.amount.to_string().parse::<Decimal>().unwrap(),
+            operation: leg.operation.clone(),
+            status: leg.status.clone(),
+            attempts: leg.attempts,
+            next_attempt_at: leg.next_attempt_at,
+            last_error: leg.last_error.clone(),
+            created_at: leg.created_at,
+            updated_at: leg.updated_at,
+        })
+        .collect();
+
+    Ok(legs_vec)
+}
+
+// records the outcome of an attempt at a leg
+pub async fn update_settlement_leg(
+    pool: &Pool<Postgres>,
+    leg: &DbOutboxLeg,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE settlement_outbox SET
+          status = $3, attempts = $4, next_attempt_at = $5, last_error = $6, updated_at = $7
+      WHERE settlement_id = $1 AND leg = $2",
+    )
+    .bind(&leg.settlement_id)
+    .bind(&leg.leg)
+    .bind(&leg.status)
+    .bind(leg.attempts)
+    .bind(leg.next_attempt_at)
+    .bind(&leg.last_error)
+    .bind(leg.updated_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// puts failed legs of a settlement, or just one of them, back in line with a fresh set of attempts
+pub async fn replay_settlement_legs(
+    pool: &Pool<Postgres>,
+    settlement_id: String,
+    leg: Option<String>,
+) -> Result<u64, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let result = sqlx::query(
+        "UPDATE settlement_outbox SET status = 'PENDING', attempts = 0, next_attempt_at = $3, updated_at = $3
+      WHERE settlement_id = $1 AND status = 'FAILED' AND ($2::VARCHAR IS NULL OR leg = $2)",
+    )
+    .bind(settlement_id)
+    .bind(leg)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
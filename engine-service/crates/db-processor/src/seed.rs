@@ -63,6 +63,11 @@ pub async fn generate_random_trades(pool: &PgPool, num_trades: i32) -> Result<()
             order_side: "BUY".to_string(), // Default for seed data
             base_asset: "BTC".to_string(), // Default for seed data
             quote_asset: "USDC".to_string(), // Default for seed data
+            taker_fee: Decimal::ZERO,
+            taker_fee_asset: "BTC".to_string(),
+            maker_fee: Decimal::ZERO,
+            maker_fee_asset: "USDC".to_string(),
+            settlement_legs: vec![],
         };
 
         // Insert the trade into the database
@@ -0,0 +1,110 @@
+use crate::query::{get_due_settlement_legs, update_settlement_leg};
+use crate::types::DbOutboxLeg;
+use crate::{balance_request, send_balance_request};
+use reqwest::Client;
+use sqlx::{Pool, Postgres};
+use std::collections::HashSet;
+use std::time::Duration;
+
+const BATCH_SIZE: i64 = 100;
+const MAX_ATTEMPTS: i32 = 10;
+const BASE_BACKOFF_MILLIS: i64 = 1000;
+const MAX_BACKOFF_MILLIS: i64 = 5 * 60 * 1000;
+
+// applies the settlement outbox to the user service - the legs of trades and of every other balance change.
+// every leg is sent with its settlement id and leg name as an idempotency key, so the user service can drop
+// a leg it already applied when a retry repeats it
+pub async fn run_settlement_worker(pool: Pool<Postgres>) {
+    let client = Client::new();
+    let user_service_url =
+        std::env::var("USER_SERVICE_URL").unwrap_or_else(|_| "http://localhost:3001".to_string());
+
+    loop {
+        if let Err(e) = apply_due_legs(&pool, &client, &user_service_url).await {
+            println!("Failed to process settlement outbox: {:?}", e);
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+}
+
+async fn apply_due_legs(
+    pool: &Pool<Postgres>,
+    client: &Client,
+    user_service_url: &str,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let legs = get_due_settlement_legs(pool, now, BATCH_SIZE).await?;
+
+    // once a leg fails, the later legs of its settlement wait for it
+    let mut blocked_settlements: HashSet<String> = HashSet::new();
+    for mut leg in legs {
+        if blocked_settlements.contains(&leg.settlement_id) {
+            continue;
+        }
+
+        let result = apply_leg(client, user_service_url, &leg)
+            .await
+            .map_err(|e| e.to_string());
+
+        let now = chrono::Utc::now().timestamp_millis();
+        leg.attempts += 1;
+        leg.updated_at = now;
+        match result {
+            Ok(()) => {
+                leg.status = "APPLIED".to_string();
+                leg.last_error = None;
+            }
+            Err(e) => {
+                println!(
+                    "Failed to apply settlement leg {} of {} (attempt {}): {}",
+                    leg.leg, leg.settlement_id, leg.attempts, e
+                );
+                blocked_settlements.insert(leg.settlement_id.clone());
+
+                // doubles the wait after every attempt, and leaves the leg to an operator once they run out
+                let backoff = BASE_BACKOFF_MILLIS
+                    .saturating_mul(1 << (leg.attempts - 1).min(20))
+                    .min(MAX_BACKOFF_MILLIS);
+                leg.next_attempt_at = now + backoff;
+                leg.last_error = Some(e);
+                if leg.attempts >= MAX_ATTEMPTS {
+                    leg.status = "FAILED".to_string();
+                }
+            }
+        }
+
+        update_settlement_leg(pool, &leg).await?;
+    }
+
+    Ok(())
+}
+
+async fn apply_leg(
+    client: &Client,
+    user_service_url: &str,
+    leg: &DbOutboxLeg,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (path, operation) = match leg.operation.as_str() {
+        "add" | "subtract" => ("update", Some(leg.operation.as_str())),
+        "lock" => ("lock", None),
+        "unlock" => ("unlock", None),
+        _ => return Err(format!("Unknown settlement operation {}", leg.operation).into()),
+    };
+
+    let request = balance_request(
+        client,
+        user_service_url,
+        path,
+        &leg.user_id,
+        &leg.asset,
+        leg.amount,
+        operation,
+    )?
+    .header(
+        "x-idempotency-key",
+        format!("{}:{}", leg.settlement_id, leg.leg),
+    );
+
+    send_balance_request(request, path).await
+}
@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 pub enum DatabaseRequests {
     InsertTrade(DbTrade),
     InsertOrder(DbOrder),
+    UpsertMarket(DbMarket),
+    UpsertAsset(DbAsset),
+    UpdateBalance(DbBalance),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +27,30 @@ pub struct DbTrade {
     pub base_asset: String,
     #[serde(default)]
     pub quote_asset: String,
+    // fees are charged in the asset each side received
+    #[serde(default)]
+    pub taker_fee: Decimal,
+    #[serde(default)]
+    pub taker_fee_asset: String,
+    #[serde(default)]
+    pub maker_fee: Decimal,
+    #[serde(default)]
+    pub maker_fee_asset: String,
+    // the balance movements the trade settled, written to the settlement outbox with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settlement_legs: Vec<DbSettlementLeg>,
+}
+
+// one movement on one user's balance in the user service - legs of a trade or balance change are applied
+// in `seq` order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbSettlementLeg {
+    pub leg: String,
+    pub seq: i32,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub operation: String, // add, subtract, lock or unlock
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +65,86 @@ pub struct DbOrder {
     pub order_type: String,
     pub order_status: String,
     pub timestamp: i64,
+    #[serde(default = "default_time_in_force")]
+    pub time_in_force: String,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub trigger_price: Option<Decimal>,
+    #[serde(default)]
+    pub trailing_offset: Option<Decimal>,
+    #[serde(default)]
+    pub trailing_percent: Option<Decimal>,
+    #[serde(default)]
+    pub oco_group_id: Option<String>,
+    #[serde(default)]
+    pub display_quantity: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbMarket {
+    pub symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub min_order_size: Decimal,
+    pub max_order_size: Decimal,
+    pub price_precision: i32,
+    pub quantity_precision: i32,
+    pub status: String,
+    #[serde(default = "default_trading_state")]
+    pub trading_state: String,
+    #[serde(default)]
+    pub price_band_percent: Option<Decimal>,
+    #[serde(default)]
+    pub reference_price: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_percent: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default)]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(default = "default_matching_policy")]
+    pub matching_policy: String,
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbAsset {
+    pub symbol: String,
+    pub decimals: i32,
+    pub status: String,
+}
+
+// a user's balance of one asset after a ledger change. a change that did not settle a trade carries the
+// legs that replay it on the user service, written to the settlement outbox under its id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbBalance {
+    pub user_id: String,
+    pub asset: String,
+    pub available: Decimal,
+    pub locked: Decimal,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub change_id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settlement_legs: Vec<DbSettlementLeg>,
+}
+
+// a balance as the user service kept it before the engine owned the ledger - `balance` is the total,
+// of which `locked_balance` is locked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserServiceBalance {
+    pub user_id: String,
+    pub currency: String,
+    pub balance: Decimal,
+    pub locked_balance: Decimal,
+}
+
+fn default_time_in_force() -> String {
+    "GTC".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +160,35 @@ pub struct KlineData {
     pub volume: String,
 }
 
+// a settlement leg as the outbox tracks it - PENDING until the user service applied it, FAILED once
+// it ran out of attempts and waits for an operator to replay it. the settlement is `market:trade_id`
+// for a trade and `balance:change_id` for a deposit, withdrawal, lock or unlock
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbOutboxLeg {
+    pub settlement_id: String,
+    pub leg: String,
+    pub seq: i32,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub operation: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+// quote volume a user traded on the day starting at `timestamp`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbTradeVolume {
+    pub user_id: String,
+    pub timestamp: i64,
+    pub volume: Decimal,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TickerData {
@@ -67,3 +203,11 @@ pub struct TickerData {
     pub trades: String,
     pub volume: String,
 }
+
+fn default_trading_state() -> String {
+    "OPEN".to_string()
+}
+
+fn default_matching_policy() -> String {
+    "FIFO".to_string()
+}
@@ -1,7 +1,8 @@
 use super::engine::Engine;
+use super::ledger::BalanceChange;
 use crate::types::{
-    db::{DatabaseRequests, DbOrder, DbTrade},
-    engine::{Fill, Order, OrderSide},
+    db::{DatabaseRequests, DbAsset, DbBalance, DbMarket, DbOrder, DbSettlementLeg, DbTrade},
+    engine::{AssetConfig, Fill, MarketConfig, Order, OrderSide},
 };
 use async_trait::async_trait;
 use redis::{RedisManager, RedisQueues};
@@ -28,6 +29,10 @@ pub trait DbUpdates {
         fills: &Vec<Fill>,
         redis_conn: &RedisManager,
     );
+    async fn update_db_market(&self, market: &MarketConfig, redis_conn: &RedisManager);
+    async fn update_db_asset(&self, asset: &AssetConfig, redis_conn: &RedisManager);
+    async fn update_db_balance(&self, change: &BalanceChange, redis_conn: &RedisManager);
+    async fn update_db_settled_balance(&self, change: &BalanceChange, redis_conn: &RedisManager);
 }
 
 #[async_trait]
@@ -51,6 +56,13 @@ impl DbUpdates for Engine {
             order_type: format!("{:?}", order.order_type),
             order_status: format!("{:?}", order.order_status),
             timestamp: order.timestamp,
+            time_in_force: format!("{:?}", order.time_in_force),
+            expires_at: order.expires_at,
+            trigger_price: order.trigger_price,
+            trailing_offset: order.trailing_offset,
+            trailing_percent: order.trailing_percent,
+            oco_group_id: order.oco_group_id,
+            display_quantity: order.display_quantity,
         };
 
         let create_db_order_request = DatabaseRequests::InsertOrder(db_order);
@@ -76,6 +88,12 @@ impl DbUpdates for Engine {
         fills: &Vec<Fill>,
         redis_conn: &RedisManager,
     ) {
+        // each side pays its fee in the asset it received
+        let (taker_fee_asset, maker_fee_asset) = match order_side {
+            OrderSide::BUY => (base_asset.clone(), quote_asset.clone()),
+            OrderSide::SELL => (quote_asset.clone(), base_asset.clone()),
+        };
+
         for fill in fills.iter() {
             let db_trade = DbTrade {
                 trade_id: fill.trade_id,
@@ -89,6 +107,18 @@ impl DbUpdates for Engine {
                 order_side: format!("{:?}", order_side),
                 base_asset: base_asset.clone(),
                 quote_asset: quote_asset.clone(),
+                taker_fee: fill.taker_fee,
+                taker_fee_asset: taker_fee_asset.clone(),
+                maker_fee: fill.maker_fee,
+                maker_fee_asset: maker_fee_asset.clone(),
+                settlement_legs: settlement_legs(
+                    fill,
+                    &order_side,
+                    &user_id,
+                    &base_asset,
+                    &quote_asset,
+                    &self.fee_account,
+                ),
             };
 
             let create_db_trade_request = DatabaseRequests::InsertTrade(db_trade);
@@ -104,4 +134,266 @@ impl DbUpdates for Engine {
                 });
         }
     }
+
+    async fn update_db_market(&self, market: &MarketConfig, redis_conn: &RedisManager) {
+        let db_market = DbMarket {
+            symbol: market.symbol.clone(),
+            base_asset: market.base_asset.to_string(),
+            quote_asset: market.quote_asset.to_string(),
+            min_order_size: market.min_order_size,
+            max_order_size: market.max_order_size,
+            price_precision: market.price_precision as i32,
+            quantity_precision: market.quantity_precision as i32,
+            status: format!("{:?}", market.status),
+            trading_state: format!("{:?}", market.trading_state),
+            price_band_percent: market.price_band_percent,
+            reference_price: market.reference_price,
+            circuit_breaker_percent: market.circuit_breaker_percent,
+            circuit_breaker_window_secs: market.circuit_breaker_window_secs,
+            circuit_breaker_halt_secs: market.circuit_breaker_halt_secs,
+            matching_policy: format!("{:?}", market.matching_policy),
+            maker_fee_rate: market.maker_fee_rate,
+            taker_fee_rate: market.taker_fee_rate,
+        };
+
+        let upsert_db_market_request = DatabaseRequests::UpsertMarket(db_market);
+        let upsert_db_market_data = to_string(&upsert_db_market_request).unwrap();
+        let _ = redis_conn
+            .push(
+                RedisQueues::DATABASE.to_string().as_str(),
+                upsert_db_market_data,
+            )
+            .await
+            .map_err(|e| {
+                println!("Couldn't push market into database queue - {}", e);
+            });
+    }
+
+    async fn update_db_asset(&self, asset: &AssetConfig, redis_conn: &RedisManager) {
+        let db_asset = DbAsset {
+            symbol: asset.symbol.clone(),
+            decimals: asset.decimals as i32,
+            status: format!("{:?}", asset.status),
+        };
+
+        let upsert_db_asset_request = DatabaseRequests::UpsertAsset(db_asset);
+        let upsert_db_asset_data = to_string(&upsert_db_asset_request).unwrap();
+        let _ = redis_conn
+            .push(
+                RedisQueues::DATABASE.to_string().as_str(),
+                upsert_db_asset_data,
+            )
+            .await
+            .map_err(|e| {
+                println!("Couldn't push asset into database queue - {}", e);
+            });
+    }
+
+    async fn update_db_balance(&self, change: &BalanceChange, redis_conn: &RedisManager) {
+        push_db_balance(change, false, redis_conn).await;
+    }
+
+    // a change settling a trade is persisted like any other - the user service gets it from the settlement outbox
+    async fn update_db_settled_balance(&self, change: &BalanceChange, redis_conn: &RedisManager) {
+        push_db_balance(change, true, redis_conn).await;
+    }
+}
+
+async fn push_db_balance(
+    change: &BalanceChange,
+    settled_by_trade: bool,
+    redis_conn: &RedisManager,
+) {
+    let db_balance = DbBalance {
+        user_id: change.user_id.clone(),
+        asset: change.asset.clone(),
+        available: change.balance.available,
+        locked: change.balance.locked,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        change_id: uuid::Uuid::new_v4().to_string(),
+        settlement_legs: if settled_by_trade {
+            vec![]
+        } else {
+            balance_change_legs(change)
+        },
+    };
+
+    let update_db_balance_request = DatabaseRequests::UpdateBalance(db_balance);
+    let update_db_balance_data = to_string(&update_db_balance_request).unwrap();
+    let _ = redis_conn
+        .push(
+            RedisQueues::DATABASE.to_string().as_str(),
+            update_db_balance_data,
+        )
+        .await
+        .map_err(|e| {
+            println!("Couldn't push balance into database queue - {}", e);
+        });
+}
+
+// what a balance change that did not settle a trade moves on the user service's balances. the user service
+// keeps the total and the locked part of it, so the change is replayed as a credit, a lock or unlock and a
+// debit, in that order so none of its checks fail halfway through
+fn balance_change_legs(change: &BalanceChange) -> Vec<DbSettlementLeg> {
+    let total_change = change.available_change + change.locked_change;
+
+    [
+        ("credit", total_change, "add"),
+        ("lock", change.locked_change, "lock"),
+        ("unlock", -change.locked_change, "unlock"),
+        ("debit", -total_change, "subtract"),
+    ]
+    .into_iter()
+    .filter(|(_, amount, _)| *amount > Decimal::ZERO)
+    .enumerate()
+    .map(|(seq, (leg, amount, operation))| DbSettlementLeg {
+        leg: leg.to_string(),
+        seq: seq as i32,
+        user_id: change.user_id.clone(),
+        asset: change.asset.clone(),
+        amount,
+        operation: operation.to_string(),
+    })
+    .collect()
+}
+
+// what a fill moves on the user service's balances, in the order it has to be applied - the buyer pays the
+// quote it locked, the seller the base it locked, and each receives the other side less its fee
+fn settlement_legs(
+    fill: &Fill,
+    order_side: &OrderSide,
+    user_id: &str,
+    base_asset: &str,
+    quote_asset: &str,
+    fee_account: &str,
+) -> Vec<DbSettlementLeg> {
+    let (buyer_id, seller_id, buyer_fee, seller_fee) = match order_side {
+        OrderSide::BUY => (
+            user_id,
+            fill.other_user_id.as_str(),
+            fill.taker_fee,
+            fill.maker_fee,
+        ),
+        OrderSide::SELL => (
+            fill.other_user_id.as_str(),
+            user_id,
+            fill.maker_fee,
+            fill.taker_fee,
+        ),
+    };
+    let cost = fill.price * fill.quantity;
+
+    [
+        ("buyer_unlock", buyer_id, quote_asset, cost, "unlock"),
+        ("buyer_debit", buyer_id, quote_asset, cost, "subtract"),
+        (
+            "buyer_credit",
+            buyer_id,
+            base_asset,
+            fill.quantity - buyer_fee,
+            "add",
+        ),
+        (
+            "seller_unlock",
+            seller_id,
+            base_asset,
+            fill.quantity,
+            "unlock",
+        ),
+        (
+            "seller_debit",
+            seller_id,
+            base_asset,
+            fill.quantity,
+            "subtract",
+        ),
+        (
+            "seller_credit",
+            seller_id,
+            quote_asset,
+            cost - seller_fee,
+            "add",
+        ),
+        ("buyer_fee", fee_account, base_asset, buyer_fee, "add"),
+        ("seller_fee", fee_account, quote_asset, seller_fee, "add"),
+    ]
+    .into_iter()
+    .filter(|(_, _, _, amount, _)| *amount > Decimal::ZERO)
+    .enumerate()
+    .map(
+        |(seq, (leg, user_id, asset, amount, operation))| DbSettlementLeg {
+            leg: leg.to_string(),
+            seq: seq as i32,
+            user_id: user_id.to_string(),
+            asset: asset.to_string(),
+            amount,
+            operation: operation.to_string(),
+        },
+    )
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{balance_change_legs, settlement_legs};
+    use crate::engine::ledger::{Balance, BalanceChange};
+    use crate::types::engine::{Fill, OrderSide};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn settlement_legs_move_every_asset_without_creating_any() {
+        let fill = Fill {
+            price: dec!(150),
+            quantity: dec!(2),
+            trade_id: 1,
+            other_user_id: "alice".to_string(),
+            order_id: "1".to_string(),
+            taker_fee: dec!(0.3),
+            maker_fee: dec!(0.002),
+        };
+
+        let legs = settlement_legs(&fill, &OrderSide::SELL, "bob", "SOL", "USDC", "fees");
+
+        for asset in ["SOL", "USDC"] {
+            let net: Decimal = legs
+                .iter()
+                .filter(|leg| leg.asset == asset)
+                .map(|leg| match leg.operation.as_str() {
+                    "add" => leg.amount,
+                    "subtract" => -leg.amount,
+                    _ => dec!(0),
+                })
+                .sum();
+            assert_eq!(net, dec!(0), "{} is not conserved", asset);
+        }
+
+        let seqs: Vec<i32> = legs.iter().map(|leg| leg.seq).collect();
+        assert_eq!(seqs, (0..8).collect::<Vec<i32>>());
+        assert!(legs.iter().any(|leg| leg.leg == "buyer_credit"
+            && leg.user_id == "alice"
+            && leg.amount == dec!(1.998)));
+    }
+
+    #[test]
+    fn balance_change_legs_replay_a_lock_and_a_withdrawal() {
+        let change = |available_change, locked_change| BalanceChange {
+            user_id: "alice".to_string(),
+            asset: "USDC".to_string(),
+            available_change,
+            locked_change,
+            balance: Balance::default(),
+        };
+
+        let legs = balance_change_legs(&change(dec!(-50), dec!(50)));
+        assert_eq!(legs.len(), 1);
+        assert_eq!((legs[0].leg.as_str(), legs[0].amount), ("lock", dec!(50)));
+
+        let legs = balance_change_legs(&change(dec!(-20), dec!(0)));
+        assert_eq!(legs.len(), 1);
+        assert_eq!(
+            (legs[0].operation.as_str(), legs[0].amount),
+            ("subtract", dec!(20))
+        );
+    }
 }
@@ -1,74 +1,259 @@
 use crate::engine::db::DbUpdates;
+use crate::engine::fees::{FeeSchedule, FeeTier};
+use crate::engine::ledger::{open_funds, Balance, Ledger, Settlement};
 use crate::engine::orderbook::OrderBook;
+use crate::engine::trigger_book::TriggerBook;
 use crate::engine::ws_stream::WsStreamUpdates;
 use crate::types::engine::{
-    Asset, AssetPair, CancelAllOrders, CancelOrder, CreateOrder, GetDepth, GetOpenOrder,
-    GetOpenOrders, Order, OrderSide, OrderStatus, OrderType, ProcessOrderResult,
+    AddMarket, Asset, AssetConfig, AssetPair, AssetStatus, CancelAllOrders, CancelOrder,
+    CancelledOrder, CreateOcoOrder, CreateOrder, Fill, GetDepth, GetFeeTier, GetOpenOrder,
+    GetOpenOrders, MarketConfig, MarketStatus, MatchingPolicy, ModifyOrder, OcoOrderResult, Order,
+    OrderSide, OrderStatus, OrderType, ProcessOrderResult, SelfTradePrevention, SetMatchingPolicy,
+    SetPriceProtection, SetTradingState, TimeInForce, TradingState, UpdateMarketStatus,
+    UpsertAsset,
+};
+use db_processor::get_user_service_balances;
+use db_processor::query::{
+    get_assets_from_db, get_balances_from_db, get_latest_trade_id_from_db,
+    get_latest_trade_price_from_db, get_markets_from_db, get_orders_from_db,
+    get_trade_volumes_from_db, import_balances, is_ledger_imported, seed_assets, seed_markets,
 };
-use crate::user_service::UserServiceClient;
-use db_processor::query::{get_latest_trade_id_from_db, get_orders_from_db};
 use redis::RedisManager;
-use rust_decimal::Decimal;
-use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
 use rust_decimal_macros::dec;
-use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 use std::collections::HashMap;
-use std::sync::Mutex;
-
-pub enum AmountType {
-    AVAILABLE,
-    LOCKED,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Amount {
-    available: Decimal,
-    locked: Decimal,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct UserBalances {
-    user_id: String,
-    balance: HashMap<Asset, Amount>,
-}
 
 #[derive(Debug)]
 pub struct Engine {
     pub orderbooks: Vec<OrderBook>,
-    pub balances: HashMap<String, Mutex<UserBalances>>,
-    pub user_service_client: UserServiceClient,
+    pub trigger_books: HashMap<String, TriggerBook>, // market -> stop and take profit orders
+    pub oco_groups: HashMap<String, Vec<String>>,    // oco group id -> ids of its live legs
+    pub assets: HashMap<String, AssetConfig>,        // asset symbol -> decimals and status
+    pub ledger: Ledger,                              // available and locked funds of every user
+    pub fees: FeeSchedule,                           // volume tiers and 30-day volume of every user
+    pub fee_account: String,                         // the account collected fees are credited to
+    pub auction_secs: i64, // how long opening and reopening auctions collect orders
 }
 
 impl Engine {
     pub fn new() -> Engine {
         Engine {
             orderbooks: vec![],
-            balances: HashMap::new(),
-            user_service_client: UserServiceClient::new(),
+            trigger_books: HashMap::new(),
+            oco_groups: HashMap::new(),
+            assets: HashMap::new(),
+            ledger: Ledger::new(),
+            fees: FeeSchedule::default(),
+            fee_account: std::env::var("FEE_ACCOUNT_ID").unwrap_or_else(|_| "fees".to_string()),
+            auction_secs: std::env::var("AUCTION_DURATION_SECS")
+                .ok()
+                .and_then(|auction_secs| auction_secs.parse().ok())
+                .unwrap_or(60),
         }
     }
 
     pub async fn init_engine(&mut self, pool: &Pool<Postgres>) {
-        // Load markets from configuration
-        let markets_config = vec![
-            ("SOL_USDC", Asset::SOL, Asset::USDC),
-            ("BTC_USDC", Asset::BTC, Asset::USDC),
-            ("ETH_USDC", Asset::ETH, Asset::USDC),
-            ("SOL_USDT", Asset::SOL, Asset::USDT),
-        ];
-
-        for (market_symbol, base, quote) in markets_config {
-            let trade_id: i64 = get_latest_trade_id_from_db(pool, market_symbol.to_string())
+        // Seed the asset registry from configuration - assets already in it keep their admin changes
+        let assets_path =
+            std::env::var("ASSETS_CONFIG_PATH").unwrap_or_else(|_| "assets.json".to_string());
+        let assets_config: Vec<AssetConfig> = serde_json::from_str(
+            &std::fs::read_to_string(&assets_path).expect("Failed to read assets config"),
+        )
+        .expect("Failed to parse assets config");
+
+        let seed = assets_config
+            .iter()
+            .map(|asset_config| db_processor::types::DbAsset {
+                symbol: asset_config.symbol.clone(),
+                decimals: asset_config.decimals as i32,
+                status: format!("{:?}", asset_config.status),
+            })
+            .collect();
+        seed_assets(pool, seed)
+            .await
+            .expect("Failed to seed asset registry");
+
+        let db_assets = get_assets_from_db(pool)
+            .await
+            .expect("Failed to load asset registry");
+
+        for db_asset in db_assets {
+            let asset_config = AssetConfig {
+                symbol: db_asset.symbol.clone(),
+                decimals: db_asset.decimals as u32,
+                status: match db_asset.status.as_str() {
+                    "DISABLED" => AssetStatus::DISABLED,
+                    _ => AssetStatus::ACTIVE,
+                },
+            };
+            self.assets.insert(db_asset.symbol, asset_config);
+        }
+        println!("✓ Loaded {} assets", self.assets.len());
+
+        let mut db_balances = get_balances_from_db(pool)
+            .await
+            .expect("Failed to load balance ledger");
+        // the first start takes over the balances the user service kept until now, once. a ledger that
+        // already holds balances from before the import was recorded only records it
+        if !is_ledger_imported(pool, "user-service")
+            .await
+            .expect("Failed to load ledger imports")
+        {
+            let imported = if db_balances.is_empty() {
+                Self::fetch_user_service_balances().await
+            } else {
+                vec![]
+            };
+            import_balances(pool, "user-service", imported.clone())
+                .await
+                .expect("Failed to persist imported balances");
+            println!(
+                "✓ Imported {} balances from the user service",
+                imported.len()
+            );
+            db_balances.extend(imported);
+        }
+        for db_balance in db_balances.iter() {
+            self.ledger.load(
+                &db_balance.user_id,
+                &db_balance.asset,
+                Balance {
+                    available: db_balance.available,
+                    locked: db_balance.locked,
+                },
+            );
+        }
+        println!("✓ Loaded {} balances", db_balances.len());
+
+        // Fee tiers come from configuration, the volume that places users in them from past trades
+        let fee_tiers_path =
+            std::env::var("FEE_TIERS_CONFIG_PATH").unwrap_or_else(|_| "fee_tiers.json".to_string());
+        let fee_tiers: Vec<FeeTier> = serde_json::from_str(
+            &std::fs::read_to_string(&fee_tiers_path).expect("Failed to read fee tiers config"),
+        )
+        .expect("Failed to parse fee tiers config");
+        self.fees = FeeSchedule::new(fee_tiers);
+
+        let since = chrono::Utc::now().timestamp_millis() - 30 * 24 * 60 * 60 * 1000;
+        let db_trade_volumes = get_trade_volumes_from_db(pool, since)
+            .await
+            .expect("Failed to load trade volumes");
+        for db_trade_volume in db_trade_volumes {
+            self.fees.record_volume(
+                &db_trade_volume.user_id,
+                db_trade_volume.timestamp,
+                db_trade_volume.volume,
+            );
+        }
+        println!("✓ Loaded {} fee tiers", self.fees.tiers.len());
+
+        // Seed the market registry from configuration - markets already in it keep their admin changes
+        let markets_path =
+            std::env::var("MARKETS_CONFIG_PATH").unwrap_or_else(|_| "markets.json".to_string());
+        let markets_config: Vec<MarketConfig> = serde_json::from_str(
+            &std::fs::read_to_string(&markets_path).expect("Failed to read markets config"),
+        )
+        .expect("Failed to parse markets config");
+
+        let seed = markets_config
+            .iter()
+            .map(|market_config| db_processor::types::DbMarket {
+                symbol: market_config.symbol.clone(),
+                base_asset: market_config.base_asset.to_string(),
+                quote_asset: market_config.quote_asset.to_string(),
+                min_order_size: market_config.min_order_size,
+                max_order_size: market_config.max_order_size,
+                price_precision: market_config.price_precision as i32,
+                quantity_precision: market_config.quantity_precision as i32,
+                status: format!("{:?}", market_config.status),
+                trading_state: format!("{:?}", market_config.trading_state),
+                price_band_percent: market_config.price_band_percent,
+                reference_price: market_config.reference_price,
+                circuit_breaker_percent: market_config.circuit_breaker_percent,
+                circuit_breaker_window_secs: market_config.circuit_breaker_window_secs,
+                circuit_breaker_halt_secs: market_config.circuit_breaker_halt_secs,
+                matching_policy: format!("{:?}", market_config.matching_policy),
+                maker_fee_rate: market_config.maker_fee_rate,
+                taker_fee_rate: market_config.taker_fee_rate,
+            })
+            .collect();
+        seed_markets(pool, seed)
+            .await
+            .expect("Failed to seed market registry");
+
+        // Load markets and their order filters from the registry
+        let db_markets = get_markets_from_db(pool)
+            .await
+            .expect("Failed to load market registry");
+
+        for db_market in db_markets {
+            let market_config = MarketConfig {
+                symbol: db_market.symbol.clone(),
+                base_asset: match self.get_asset(&db_market.base_asset) {
+                    Ok(asset) => asset,
+                    Err(e) => {
+                        println!("Skipping market {} - {}", db_market.symbol, e);
+                        continue;
+                    }
+                },
+                quote_asset: match self.get_asset(&db_market.quote_asset) {
+                    Ok(asset) => asset,
+                    Err(e) => {
+                        println!("Skipping market {} - {}", db_market.symbol, e);
+                        continue;
+                    }
+                },
+                min_order_size: db_market.min_order_size,
+                max_order_size: db_market.max_order_size,
+                price_precision: db_market.price_precision as u32,
+                quantity_precision: db_market.quantity_precision as u32,
+                status: match db_market.status.as_str() {
+                    "PAUSED" => MarketStatus::PAUSED,
+                    "DELISTED" => MarketStatus::DELISTED,
+                    _ => MarketStatus::TRADING,
+                },
+                trading_state: match db_market.trading_state.as_str() {
+                    "POST_ONLY" => TradingState::POST_ONLY,
+                    "CANCEL_ONLY" => TradingState::CANCEL_ONLY,
+                    "HALTED" => TradingState::HALTED,
+                    "AUCTION" => TradingState::AUCTION,
+                    _ => TradingState::OPEN,
+                },
+                price_band_percent: db_market.price_band_percent,
+                reference_price: db_market.reference_price,
+                circuit_breaker_percent: db_market.circuit_breaker_percent,
+                circuit_breaker_window_secs: db_market.circuit_breaker_window_secs,
+                circuit_breaker_halt_secs: db_market.circuit_breaker_halt_secs,
+                matching_policy: match db_market.matching_policy.as_str() {
+                    "PRO_RATA" => MatchingPolicy::PRO_RATA,
+                    "HYBRID" => MatchingPolicy::HYBRID,
+                    _ => MatchingPolicy::FIFO,
+                },
+                maker_fee_rate: db_market.maker_fee_rate,
+                taker_fee_rate: db_market.taker_fee_rate,
+            };
+
+            let market_symbol = market_config.symbol.clone();
+            let trade_id: i64 = get_latest_trade_id_from_db(pool, market_symbol.clone())
                 .await
                 .unwrap_or(0);
 
-            let orderbook = OrderBook::new(
-                AssetPair { base, quote },
-                trade_id + 1,
-            );
+            let mut orderbook = OrderBook::new(market_config, trade_id + 1);
+            // trailing stops move with the last trade, so they pick up from the one before the restart
+            orderbook.last_trade_price =
+                get_latest_trade_price_from_db(pool, market_symbol.clone())
+                    .await
+                    .unwrap_or(None);
+            // an auction cut short by a restart collects orders for another full period
+            if orderbook.config.trading_state == TradingState::AUCTION {
+                orderbook.auction_until =
+                    Some(chrono::Utc::now().timestamp_millis() + self.auction_secs * 1000);
+            }
 
             self.orderbooks.push(orderbook);
+            self.trigger_books
+                .insert(market_symbol.clone(), TriggerBook::new());
             println!("✓ Initialized orderbook for {}", market_symbol);
         }
 
@@ -93,6 +278,12 @@ impl Engine {
                             order_type: match db_order.order_type.as_str() {
                                 "LIMIT" => OrderType::LIMIT,
                                 "MARKET" => OrderType::MARKET,
+                                "STOP_MARKET" => OrderType::STOP_MARKET,
+                                "STOP_LIMIT" => OrderType::STOP_LIMIT,
+                                "TAKE_PROFIT_MARKET" => OrderType::TAKE_PROFIT_MARKET,
+                                "TAKE_PROFIT_LIMIT" => OrderType::TAKE_PROFIT_LIMIT,
+                                "TRAILING_STOP_MARKET" => OrderType::TRAILING_STOP_MARKET,
+                                "TRAILING_STOP_LIMIT" => OrderType::TRAILING_STOP_LIMIT,
                                 _ => continue, // Skip invalid orders
                             },
                             order_status: match db_order.order_status.as_str() {
@@ -101,10 +292,50 @@ impl Engine {
                                 _ => continue, // Skip invalid orders
                             },
                             timestamp: db_order.timestamp,
+                            time_in_force: match db_order.time_in_force.as_str() {
+                                "GTC" => TimeInForce::GTC,
+                                "GTD" => TimeInForce::GTD,
+                                _ => continue, // IOC and FOK orders never rest
+                            },
+                            expires_at: db_order.expires_at,
+                            post_only: false, // already resting, so there is nothing left to check
+                            post_only_reprice: false,
+                            trigger_price: db_order.trigger_price,
+                            trailing_offset: db_order.trailing_offset,
+                            trailing_percent: db_order.trailing_percent,
+                            oco_group_id: db_order.oco_group_id.clone(),
+                            display_quantity: db_order.display_quantity,
+                            visible_quantity: None, // set by the orderbook on restore
+                            self_trade_prevention: SelfTradePrevention::default(),
                         };
 
-                        // Add order to orderbook
-                        let _ = orderbook.restore_order(order);
+                        if let Some(oco_group_id) = &order.oco_group_id {
+                            self.oco_groups
+                                .entry(oco_group_id.clone())
+                                .or_default()
+                                .push(order.order_id.clone());
+                        }
+
+                        // Trigger orders go back to the trigger book, everything else to the orderbook
+                        if order.order_type.is_trigger() {
+                            self.trigger_books
+                                .entry(market_symbol.clone())
+                                .or_default()
+                                .add_order(order);
+                        } else {
+                            // what the order reserved is still locked in the persisted balances
+                            let asset = match order.side {
+                                OrderSide::BUY => orderbook.asset_pair.quote.to_string(),
+                                OrderSide::SELL => orderbook.asset_pair.base.to_string(),
+                            };
+                            self.ledger.load_reservation(
+                                &order.order_id,
+                                &order.user_id,
+                                &asset,
+                                open_funds(&order),
+                            );
+                            let _ = orderbook.restore_order(order);
+                        }
                     }
                     println!("✓ Loaded {} orders for {}", orders.len(), market_symbol);
                 }
@@ -113,47 +344,340 @@ impl Engine {
         }
     }
 
+    // the user service may still be starting, so the export is retried with a growing backoff until it
+    // answers instead of failing the engine start
+    async fn fetch_user_service_balances() -> Vec<db_processor::types::DbBalance> {
+        let mut backoff = std::time::Duration::from_secs(1);
+        loop {
+            match get_user_service_balances().await {
+                Ok(balances) => return balances,
+                Err(e) => {
+                    eprintln!(
+                        "Failed to import balances from the user service, retrying in {:?}: {}",
+                        backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(std::time::Duration::from_secs(30));
+                }
+            }
+        }
+    }
+
+    // new users start with an empty balance of every asset, funded by deposits
     pub fn init_user_balance(&mut self, user_id: &str) {
-        let initial_balances = UserBalances {
-            user_id: user_id.to_string(),
-            balance: HashMap::new(),
-        };
+        self.ledger.open_account(user_id);
+    }
+
+    // credits funds that arrived for a user, e.g. a confirmed on-chain deposit
+    pub async fn deposit(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+        redis_conn: &RedisManager,
+    ) -> Result<Balance, &'static str> {
+        self.get_asset(asset)?;
+
+        let change = self.ledger.deposit(user_id, asset, amount)?;
+        self.update_db_balance(&change, redis_conn).await;
+
+        Ok(change.balance)
+    }
+
+    // takes funds out of a user's available balance before they are sent out - locked funds stay with their orders
+    pub async fn withdraw(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+        redis_conn: &RedisManager,
+    ) -> Result<Balance, &'static str> {
+        self.get_asset(asset)?;
+
+        let change = self.ledger.withdraw(user_id, asset, amount)?;
+        self.update_db_balance(&change, redis_conn).await;
+
+        Ok(change.balance)
+    }
+
+    pub async fn create_order(
+        &mut self,
+        mut input_order: CreateOrder,
+        redis_conn: &RedisManager,
+    ) -> Result<(String, ProcessOrderResult), &str> {
+        self.check_market_trading(&input_order.market)?;
+
+        if let TradingState::POST_ONLY = self.trading_state(&input_order.market) {
+            if !matches!(input_order.order_type, OrderType::LIMIT) {
+                return Err("Market is post-only - only limit orders are accepted");
+            }
+            input_order.post_only = true;
+        }
+        if let Some(quote_quantity) = input_order.quote_quantity {
+            input_order.quantity = self.quote_order_quantity(&input_order, quote_quantity)?;
+        }
+        self.check_order_filters(&input_order)?;
+
+        let order_id = uuid::Uuid::new_v4().to_string();
+
+        if input_order.order_type.is_trigger() {
+            self.add_trigger_order(input_order, order_id.clone(), redis_conn)
+                .await?;
+
+            return Ok((
+                order_id,
+                ProcessOrderResult {
+                    executed_quantity: dec!(0),
+                    fills: vec![],
+                    cancelled_quantity: dec!(0),
+                    cancelled_orders: vec![],
+                    filled_orders: vec![],
+                },
+            ));
+        }
+
+        let market = input_order.market.clone();
+        let order_result = self
+            .place_order(input_order, order_id.clone(), redis_conn)
+            .await?;
 
-        // Add dummy values for USDC and SOL
-        let usdc_balance = Amount {
-            available: Decimal::new(1000000, 0), // Dummy value: 1000000 USDC
-            locked: Decimal::new(0, 0),          // 0 locked
+        self.fire_trigger_orders(&market, &order_result.fills, redis_conn)
+            .await;
+
+        Ok((order_id, order_result))
+    }
+
+    // looks an asset up in the registry
+    fn get_asset(&self, symbol: &str) -> Result<Asset, &'static str> {
+        match self.assets.get(symbol) {
+            Some(asset_config) => Ok(Asset(asset_config.symbol.clone())),
+            None => Err("Unsupported asset"),
+        }
+    }
+
+    fn check_asset_active(&self, asset: &Asset) -> Result<(), &'static str> {
+        match self.assets.get(&asset.0) {
+            Some(asset_config) if asset_config.status == AssetStatus::ACTIVE => Ok(()),
+            Some(_) => Err("Asset is disabled"),
+            None => Err("Unsupported asset"),
+        }
+    }
+
+    // new orders and amends need a market that is trading - cancels and reads work in every state
+    fn check_market_trading(&self, market: &str) -> Result<(), &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .ok_or("No matching orderbook found")?;
+
+        match orderbook.config.status {
+            MarketStatus::TRADING => {}
+            MarketStatus::PAUSED => return Err("Market is paused"),
+            MarketStatus::DELISTED => return Err("Market is delisted"),
+        }
+
+        // post-only markets still take orders, create_order makes sure they rest. auctions collect them
+        match orderbook.config.trading_state {
+            TradingState::OPEN | TradingState::POST_ONLY | TradingState::AUCTION => {}
+            TradingState::CANCEL_ONLY => return Err("Market is cancel-only"),
+            TradingState::HALTED => return Err("Market is halted"),
+        }
+
+        self.check_asset_active(&orderbook.asset_pair.base)?;
+        self.check_asset_active(&orderbook.asset_pair.quote)
+    }
+
+    // cancels work in every trading state except halted
+    fn check_market_cancellable(&self, market: &str) -> Result<(), &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .ok_or("No matching orderbook found")?;
+
+        match orderbook.config.trading_state {
+            TradingState::HALTED => Err("Market is halted"),
+            _ => Ok(()),
+        }
+    }
+
+    fn trading_state(&self, market: &str) -> TradingState {
+        self.orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .map(|orderbook| orderbook.config.trading_state.clone())
+            .unwrap_or_default()
+    }
+
+    // rejects orders that break the market's size or precision filters
+    fn check_order_filters(&self, input_order: &CreateOrder) -> Result<(), &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == input_order.market)
+            .ok_or("No matching orderbook found")?;
+
+        let price = match input_order.order_type {
+            OrderType::LIMIT
+            | OrderType::STOP_LIMIT
+            | OrderType::TAKE_PROFIT_LIMIT
+            | OrderType::TRAILING_STOP_LIMIT => Some(input_order.price),
+            _ => None,
         };
+        orderbook.check_filters(price, input_order.quantity)?;
+
+        if let Some(trigger_price) = input_order.trigger_price {
+            orderbook.check_filters(Some(trigger_price), input_order.quantity)?;
+        }
+
+        if let Some(display_quantity) = input_order.display_quantity {
+            if display_quantity.normalize().scale() > orderbook.config.quantity_precision {
+                return Err(
+                    "Display quantity has more decimals than the market's quantity precision",
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // the base quantity a market buy for a quote amount gets off the asks right now, so the order is
+    // matched, locked and settled like any other market buy and never spends more than the amount
+    fn quote_order_quantity(
+        &self,
+        input_order: &CreateOrder,
+        quote_quantity: Decimal,
+    ) -> Result<Decimal, &'static str> {
+        if !matches!(
+            (&input_order.order_type, &input_order.side),
+            (OrderType::MARKET, OrderSide::BUY)
+        ) {
+            return Err("Quote quantity is only supported for market buys");
+        }
+
+        if quote_quantity <= dec!(0) {
+            return Err("Quote quantity must be positive");
+        }
+
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == input_order.market)
+            .ok_or("No matching orderbook found")?;
+
+        if let Some(quote_asset) = self.assets.get(&orderbook.asset_pair.quote.0) {
+            if quote_quantity.normalize().scale() > quote_asset.decimals {
+                return Err("Quote quantity has more decimals than the quote asset");
+            }
+        }
+
+        let order = Self::build_order(input_order, String::new());
+        let quantity = orderbook.market_buy_quantity(&order, quote_quantity);
+        if quantity <= dec!(0) {
+            return Err("Quote quantity does not buy a single lot");
+        }
+
+        Ok(quantity)
+    }
+
+    fn build_order(input_order: &CreateOrder, order_id: String) -> Order {
+        Order {
+            price: input_order.price,
+            quantity: input_order.quantity,
+            filled_quantity: dec!(0),
+            order_id,
+            user_id: input_order.user_id.clone(),
+            side: input_order.side.clone(),
+            order_type: input_order.order_type.clone(),
+            order_status: OrderStatus::Pending,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            time_in_force: input_order.time_in_force.clone(),
+            expires_at: match input_order.time_in_force {
+                TimeInForce::GTD => input_order.expires_at,
+                _ => None,
+            },
+            post_only: input_order.post_only,
+            post_only_reprice: input_order.post_only_reprice,
+            trigger_price: input_order.trigger_price,
+            trailing_offset: input_order.trailing_offset,
+            trailing_percent: input_order.trailing_percent,
+            oco_group_id: input_order.oco_group_id.clone(),
+            display_quantity: input_order.display_quantity,
+            visible_quantity: None,
+            self_trade_prevention: input_order.self_trade_prevention.clone(),
+        }
+    }
 
-        let sol_balance = Amount {
-            available: Decimal::new(10000, 0), // Dummy value: 10000 SOL
-            locked: Decimal::new(0, 0),        // 0 locked
+    // iceberg orders have to be able to rest, once triggered if they are trigger orders
+    fn validate_display_quantity(input_order: &CreateOrder) -> Result<(), &'static str> {
+        let display_quantity = match input_order.display_quantity {
+            Some(display_quantity) => display_quantity,
+            None => return Ok(()),
         };
 
-        // Initialize the balance HashMap for the user
-        let mut balances_map = initial_balances.balance;
-        balances_map.insert(Asset::USDC, usdc_balance);
-        balances_map.insert(Asset::SOL, sol_balance);
-
-        // Add the initialized UserBalances to the Engine's balances map
-        self.balances.insert(
-            user_id.to_string(),
-            Mutex::new(UserBalances {
-                user_id: user_id.to_string(),
-                balance: balances_map,
-            }),
-        );
+        match (
+            input_order.order_type.triggered_order_type(),
+            &input_order.time_in_force,
+        ) {
+            (OrderType::LIMIT, TimeInForce::GTC) | (OrderType::LIMIT, TimeInForce::GTD) => {}
+            _ => return Err("Iceberg orders must be GTC or GTD limit orders"),
+        }
+
+        if display_quantity <= dec!(0) || display_quantity >= input_order.quantity {
+            return Err("Display quantity must be positive and below the order quantity");
+        }
+
+        Ok(())
     }
 
-    pub async fn create_order(
+    // matches the order against the book, settles what did not rest and publishes the result
+    async fn place_order(
         &mut self,
-        input_order: CreateOrder,
+        mut input_order: CreateOrder,
+        order_id: String,
         redis_conn: &RedisManager,
-    ) -> Result<String, &str> {
-        let funds_check = self.check_and_lock_funds(&input_order).await;
+    ) -> Result<ProcessOrderResult, &'static str> {
+        if let TimeInForce::GTD = input_order.time_in_force {
+            match input_order.expires_at {
+                Some(expires_at) if expires_at > chrono::Utc::now().timestamp_millis() => {}
+                _ => return Err("GTD order requires an expiry in the future"),
+            }
+        }
+
+        Self::validate_display_quantity(&input_order)?;
+
+        if input_order.post_only {
+            if let OrderType::MARKET = input_order.order_type {
+                return Err("Market orders cannot be post-only");
+            }
+
+            let orderbook = self
+                .orderbooks
+                .iter()
+                .find(|orderbook| orderbook.ticker() == input_order.market)
+                .ok_or("No matching orderbook found")?;
+
+            // reprice before locking funds so the lock matches the price the order will rest at
+            input_order.price = orderbook.post_only_price(
+                &input_order.side,
+                input_order.price,
+                input_order.post_only_reprice,
+            )?;
+        }
+
+        self.check_and_lock_funds(&input_order, &order_id, redis_conn)
+            .await?;
 
-        if funds_check.is_err() {
-            return Err("Funds check failed");
+        let mut order = Self::build_order(&input_order, order_id);
+
+        // every fill the order is about to make has to settle, so a trade the ledger would reject never
+        // changes the book
+        if let Err(reason) = self.check_fills_settle(&input_order.market, &order) {
+            self.release_surplus(&order.order_id, dec!(0), redis_conn)
+                .await;
+
+            return Err(reason);
         }
 
         let orderbook = match self
@@ -171,27 +695,89 @@ impl Engine {
             }
         };
 
-        let assets: Vec<&str> = input_order.market.split('_').collect();
-        let base_asset = Asset::from_str(assets[0]).unwrap();
-        let quote_asset = Asset::from_str(assets[1]).unwrap();
-        let order_id = uuid::Uuid::new_v4().to_string();
+        let asset_pair = orderbook.asset_pair.clone();
+        let base_asset = asset_pair.base.clone();
+        let quote_asset = asset_pair.quote.clone();
 
-        let order = Order {
-            price: input_order.price,
-            quantity: input_order.quantity,
-            filled_quantity: dec!(0),
-            order_id: order_id.clone(),
-            user_id: input_order.user_id.clone(),
-            side: input_order.side.clone(),
-            order_type: input_order.order_type.clone(),
-            order_status: OrderStatus::Pending,
-            timestamp: chrono::Utc::now().timestamp_millis(),
-        };
+        let mut order_result: ProcessOrderResult = match orderbook.process_order(order.clone()) {
+            Ok(order_result) => order_result,
+            Err(reason) => {
+                self.release_surplus(&order.order_id, dec!(0), redis_conn)
+                    .await;
 
-        let order_result: ProcessOrderResult = orderbook.process_order(order.clone());
+                return Err(reason);
+            }
+        };
         println!("Current orderbook bids {:?}", orderbook.bids);
         println!("Current orderbook asks {:?}", orderbook.asks);
 
+        // a trade the ledger still rejects is logged by settle_trade and never persisted or published
+        let mut settled_fills = Vec::new();
+        for mut fill in order_result.fills.drain(..) {
+            let (buyer, seller) = Self::trade_sides(&order, &fill);
+
+            if let Ok((buyer_fee, seller_fee)) = self
+                .settle_trade(
+                    &input_order.market,
+                    Some(order.side.clone()),
+                    buyer,
+                    seller,
+                    &fill,
+                    redis_conn,
+                )
+                .await
+            {
+                (fill.taker_fee, fill.maker_fee) = match order.side {
+                    OrderSide::BUY => (buyer_fee, seller_fee),
+                    OrderSide::SELL => (seller_fee, buyer_fee),
+                };
+                settled_fills.push(fill);
+            }
+        }
+        order_result.fills = settled_fills;
+
+        if matches!(order.order_type, OrderType::MARKET)
+            || order_result.cancelled_quantity > dec!(0)
+        {
+            // part of the order may still rest if self-trade prevention only decremented it
+            let rested_quantity =
+                order.quantity - order_result.executed_quantity - order_result.cancelled_quantity;
+
+            if rested_quantity > dec!(0) {
+                order.quantity -= order_result.cancelled_quantity;
+            } else {
+                // market, IOC and FOK orders never rest, so they are closed out here - this also keeps them out of the restored book
+                order.order_status = if order_result.cancelled_quantity > dec!(0) {
+                    OrderStatus::Cancelled
+                } else {
+                    OrderStatus::Filled
+                };
+            }
+        }
+
+        // a limit order that was not closed out above is filled outright, partly filled and resting, or untouched
+        if let OrderStatus::Pending = order.order_status {
+            if order_result.executed_quantity >= order.quantity {
+                order.order_status = OrderStatus::Filled;
+            } else if order_result.executed_quantity > dec!(0) {
+                order.order_status = OrderStatus::PartiallyFilled;
+            }
+        }
+
+        // both sides keep only what their open quantity still needs locked - a buy that filled below its
+        // limit gets the difference back, and an order that is closed gets back everything left. the taker
+        // carries no fills of its own yet, so its open funds are taken after what this match executed
+        let needed = open_funds(&Order {
+            filled_quantity: order_result.executed_quantity,
+            ..order.clone()
+        });
+        self.release_surplus(&order.order_id, needed, redis_conn)
+            .await;
+        for filled_order in order_result.filled_orders.iter() {
+            self.release_surplus(&filled_order.order_id, open_funds(filled_order), redis_conn)
+                .await;
+        }
+
         // Balance updates moved to db-processor after trade confirmation
         // let _ = self.update_user_balance(base_asset, quote_asset, order.clone(), &order_result).await;
         let _ = self
@@ -216,6 +802,18 @@ impl Engine {
             )
             .await;
 
+        // the maker side of every fill, so a restart restores resting orders with what is left of them
+        for filled_order in order_result.filled_orders.iter() {
+            self.update_db_orders(
+                filled_order.clone(),
+                input_order.market.clone(),
+                dec!(0),
+                &vec![],
+                redis_conn,
+            )
+            .await;
+        }
+
         let _ = self
             .publish_ws_trades(
                 input_order.market.clone(),
@@ -230,113 +828,955 @@ impl Engine {
             .publish_ws_depth_updates(
                 input_order.market.clone(),
                 order.price,
-                order.side,
+                order.side.clone(),
                 &order_result.fills,
                 redis_conn,
             )
             .await;
 
-        Ok(order_id)
-    }
-
-    pub fn get_open_order(&mut self, open_order: GetOpenOrder) -> Result<&Order, ()> {
-        let orderbook = match self
-            .orderbooks
-            .iter_mut()
-            .find(|orderbook| orderbook.ticker() == open_order.market)
-        {
-            Some(ob) => ob,
-            None => {
-                eprintln!(
-                    "No matching orderbook found for market: {}",
-                    open_order.market
-                );
-                return Err(());
-            }
-        };
-
-        let open_order = orderbook.get_open_order(open_order.user_id, open_order.order_id);
-
-        open_order
-    }
+        self.check_circuit_breaker(&input_order.market, &order_result.fills, redis_conn)
+            .await;
 
-    pub fn cancel_order(&mut self, cancel_order: CancelOrder) -> Result<String, &str> {
-        let orderbook = match self
-            .orderbooks
-            .iter_mut()
-            .find(|orderbook| orderbook.ticker() == cancel_order.market)
-        {
-            Some(ob) => ob,
-            None => {
-                eprintln!(
-                    "No matching orderbook found for market: {}",
-                    cancel_order.market
-                );
-                return Err("No matching orderbook found");
-            }
-        };
+        if let OrderStatus::Cancelled = order.order_status {
+            self.publish_ws_order_update(input_order.market.clone(), &order, redis_conn)
+                .await;
+        }
 
-        let assets: Vec<&str> = cancel_order.market.split('_').collect();
-        let base_asset_str = assets[0];
-        let quote_asset_str = assets[1];
-        let base_asset = Asset::from_str(base_asset_str)?;
-        let quote_asset = Asset::from_str(quote_asset_str)?;
-        let cancel_order_id = cancel_order.order_id.clone();
+        // resting orders of the same user cancelled or decremented by self-trade prevention
+        for (resting_order, cancelled_quantity) in order_result.cancelled_orders.iter() {
+            self.cancel_self_trade(
+                &input_order.market,
+                resting_order.clone(),
+                *cancelled_quantity,
+                redis_conn,
+            )
+            .await;
+        }
 
-        let result = orderbook.cancel_order(cancel_order);
+        // a fill on either leg of an OCO pair, as taker or maker, cancels the other leg
+        if order_result.executed_quantity > dec!(0) {
+            self.cancel_oco_legs(
+                &input_order.market,
+                &input_order.user_id,
+                &order.order_id,
+                redis_conn,
+            )
+            .await;
+        }
+        for fill in order_result.fills.iter() {
+            self.cancel_oco_legs(
+                &input_order.market,
+                &fill.other_user_id,
+                &fill.order_id,
+                redis_conn,
+            )
+            .await;
+        }
 
-        match result {
-            Ok(order) => {
-                let quantity = match order.side {
-                    OrderSide::BUY => (order.quantity - order.filled_quantity) * order.price,
-                    OrderSide::SELL => order.quantity - order.filled_quantity,
-                };
+        Ok(order_result)
+    }
 
-                match order.side {
-                    OrderSide::BUY => {
-                        self.update_balance_with_lock(
-                            order.user_id.clone(),
-                            quote_asset.clone(),
-                            quantity,
-                            AmountType::AVAILABLE,
-                        )?;
-
-                        self.update_balance_with_lock(
-                            order.user_id.clone(),
-                            quote_asset.clone(),
-                            -quantity,
-                            AmountType::LOCKED,
-                        )?;
-                    }
+    // releases the funds of a resting order for the quantity self-trade prevention took off it,
+    // and persists and publishes the order - it is only cancelled once nothing is left of it
+    async fn cancel_self_trade(
+        &mut self,
+        market: &str,
+        order: Order,
+        cancelled_quantity: Decimal,
+        redis_conn: &RedisManager,
+    ) {
+        println!(
+            "Self-trade prevention took {} off order {} in {}",
+            cancelled_quantity, order.order_id, market
+        );
 
-                    OrderSide::SELL => {
-                        self.update_balance_with_lock(
-                            order.user_id.clone(),
-                            base_asset.clone(),
-                            quantity,
-                            AmountType::AVAILABLE,
-                        )?;
-
-                        self.update_balance_with_lock(
-                            order.user_id.clone(),
-                            base_asset.clone(),
-                            -quantity,
-                            AmountType::LOCKED,
-                        )?;
-                    }
-                }
+        // matching marks the order cancelled only if it took it off the book
+        let cancelled = matches!(order.order_status, OrderStatus::Cancelled);
 
-                return Ok(cancel_order_id);
-            }
+        // matching already took the decrement off the order, so what it needs is what is left of it
+        self.release_surplus(&order.order_id, open_funds(&order), redis_conn)
+            .await;
 
-            Err(()) => {
-                println!("Failed to cancel order");
-                return Err("Failed to cancel order");
-            }
+        self.update_db_orders(
+            order.clone(),
+            market.to_string(),
+            dec!(0),
+            &vec![],
+            redis_conn,
+        )
+        .await;
+        self.publish_ws_order_update(market.to_string(), &order, redis_conn)
+            .await;
+        self.publish_ws_depth_updates(
+            market.to_string(),
+            order.price,
+            order.side.clone(),
+            &vec![],
+            redis_conn,
+        )
+        .await;
+
+        if cancelled {
+            self.cancel_oco_legs(market, &order.user_id, &order.order_id, redis_conn)
+                .await;
         }
     }
 
-    pub fn get_open_orders(&mut self, open_orders: GetOpenOrders) -> Vec<&Order> {
+    // places the stop leg in the trigger book and the limit leg on the orderbook, linked by a shared group id
+    pub async fn create_oco_order(
+        &mut self,
+        oco_order: CreateOcoOrder,
+        redis_conn: &RedisManager,
+    ) -> Result<OcoOrderResult, &'static str> {
+        self.check_market_trading(&oco_order.market)?;
+        if let TradingState::POST_ONLY = self.trading_state(&oco_order.market) {
+            return Err("Market is post-only - only limit orders are accepted");
+        }
+
+        let valid_prices = match oco_order.side {
+            OrderSide::BUY => oco_order.price < oco_order.trigger_price,
+            OrderSide::SELL => oco_order.price > oco_order.trigger_price,
+        };
+        if !valid_prices {
+            return Err(
+                "OCO limit price must be above the stop price for sells and below it for buys",
+            );
+        }
+
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == oco_order.market)
+            .ok_or("No matching orderbook found")?;
+        orderbook.check_filters(Some(oco_order.price), oco_order.quantity)?;
+        orderbook.check_filters(Some(oco_order.trigger_price), oco_order.quantity)?;
+        if let Some(stop_limit_price) = oco_order.stop_limit_price {
+            orderbook.check_filters(Some(stop_limit_price), oco_order.quantity)?;
+        }
+
+        let oco_group_id = uuid::Uuid::new_v4().to_string();
+        let limit_order_id = uuid::Uuid::new_v4().to_string();
+        let stop_order_id = uuid::Uuid::new_v4().to_string();
+
+        let stop_order = CreateOrder {
+            market: oco_order.market.clone(),
+            price: oco_order.stop_limit_price.unwrap_or(dec!(0)),
+            quantity: oco_order.quantity,
+            quote_quantity: None,
+            side: oco_order.side.clone(),
+            order_type: match oco_order.stop_limit_price {
+                Some(_) => OrderType::STOP_LIMIT,
+                None => OrderType::STOP_MARKET,
+            },
+            user_id: oco_order.user_id.clone(),
+            time_in_force: TimeInForce::GTC,
+            expires_at: None,
+            post_only: false,
+            post_only_reprice: false,
+            trigger_price: Some(oco_order.trigger_price),
+            trailing_offset: None,
+            trailing_percent: None,
+            oco_group_id: Some(oco_group_id.clone()),
+            display_quantity: None,
+            self_trade_prevention: SelfTradePrevention::default(),
+            pubsub_id: None,
+        };
+        let limit_order = CreateOrder {
+            price: oco_order.price,
+            order_type: OrderType::LIMIT,
+            trigger_price: None,
+            ..stop_order.clone()
+        };
+
+        self.add_trigger_order(stop_order, stop_order_id.clone(), redis_conn)
+            .await?;
+        self.oco_groups.insert(
+            oco_group_id.clone(),
+            vec![limit_order_id.clone(), stop_order_id.clone()],
+        );
+
+        match self
+            .place_order(limit_order, limit_order_id.clone(), redis_conn)
+            .await
+        {
+            Ok(order_result) => Ok(OcoOrderResult {
+                oco_group_id,
+                limit_order_id,
+                stop_order_id,
+                executed_quantity: order_result.executed_quantity,
+            }),
+            Err(reason) => {
+                // the limit leg never made it onto the book, so the stop leg goes too
+                self.cancel_oco_legs(
+                    &oco_order.market,
+                    &oco_order.user_id,
+                    &limit_order_id,
+                    redis_conn,
+                )
+                .await;
+
+                Err(reason)
+            }
+        }
+    }
+
+    // cancels every other live leg of the OCO group `order_id` belongs to, releasing what they had locked
+    async fn cancel_oco_legs(
+        &mut self,
+        market: &str,
+        user_id: &str,
+        order_id: &str,
+        redis_conn: &RedisManager,
+    ) {
+        let oco_group_id = match self
+            .oco_groups
+            .iter()
+            .find(|(_, order_ids)| order_ids.iter().any(|id| id == order_id))
+        {
+            Some((oco_group_id, _)) => oco_group_id.clone(),
+            None => return,
+        };
+        let order_ids = self.oco_groups.remove(&oco_group_id).unwrap_or_default();
+
+        for other_order_id in order_ids.iter().filter(|id| *id != order_id) {
+            println!(
+                "Cancelling OCO leg {} of group {}",
+                other_order_id, oco_group_id
+            );
+
+            // trigger legs have nothing locked until they fire
+            if let Some(mut order) = self
+                .trigger_books
+                .get_mut(market)
+                .and_then(|trigger_book| trigger_book.cancel_order(user_id, other_order_id))
+            {
+                order.order_status = OrderStatus::Cancelled;
+                self.update_db_orders(order, market.to_string(), dec!(0), &vec![], redis_conn)
+                    .await;
+                continue;
+            }
+
+            let orderbook = match self
+                .orderbooks
+                .iter_mut()
+                .find(|orderbook| orderbook.ticker() == market)
+            {
+                Some(orderbook) => orderbook,
+                None => return,
+            };
+
+            let asset_pair = orderbook.asset_pair.clone();
+            // legs that are already filled or cancelled are no longer on the book
+            if let Ok(order) = orderbook.cancel_order(user_id, other_order_id) {
+                self.cancel_resting_order(market.to_string(), asset_pair, order, redis_conn)
+                    .await;
+            }
+        }
+    }
+
+    // trigger orders are validated up front, but funds are only checked and locked once they fire
+    async fn add_trigger_order(
+        &mut self,
+        mut input_order: CreateOrder,
+        order_id: String,
+        redis_conn: &RedisManager,
+    ) -> Result<(), &'static str> {
+        Self::validate_display_quantity(&input_order)?;
+
+        if let OrderType::STOP_LIMIT
+        | OrderType::TAKE_PROFIT_LIMIT
+        | OrderType::TRAILING_STOP_LIMIT = input_order.order_type
+        {
+            if input_order.price <= dec!(0) {
+                return Err("Limit trigger orders require a price");
+            }
+        }
+
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == input_order.market)
+            .ok_or("No matching orderbook found")?;
+
+        if input_order.order_type.is_trailing() {
+            match (input_order.trailing_offset, input_order.trailing_percent) {
+                (Some(trailing_offset), None) if trailing_offset > dec!(0) => {}
+                (None, Some(trailing_percent))
+                    if trailing_percent > dec!(0) && trailing_percent < dec!(100) => {}
+                _ => {
+                    return Err(
+                        "Trailing stop orders require either a positive offset or a percentage below 100",
+                    )
+                }
+            }
+
+            // the trail starts from the last trade, so the trigger is derived rather than given
+            let last_trade_price = orderbook
+                .last_trade_price
+                .ok_or("Trailing stop orders need a trade price to trail")?;
+            let order = Self::build_order(&input_order, order_id.clone());
+            input_order.trigger_price =
+                TriggerBook::trailing_trigger_price(&order, last_trade_price, orderbook.tick_size);
+        } else {
+            input_order.trailing_offset = None;
+            input_order.trailing_percent = None;
+        }
+
+        match input_order.trigger_price {
+            Some(trigger_price) if trigger_price > dec!(0) => {}
+            _ => return Err("Trigger orders require a positive trigger price"),
+        }
+
+        let order = Self::build_order(&input_order, order_id);
+
+        // a trigger the market has already crossed would fire straight away
+        if let Some(last_trade_price) = orderbook.last_trade_price {
+            if TriggerBook::is_triggered(&order, last_trade_price, last_trade_price) {
+                return Err("Trigger price has already been crossed");
+            }
+        }
+
+        self.trigger_books
+            .entry(input_order.market.clone())
+            .or_default()
+            .add_order(order.clone());
+
+        self.update_db_orders(order, input_order.market, dec!(0), &vec![], redis_conn)
+            .await;
+
+        Ok(())
+    }
+
+    // places every trigger order crossed by the fills, and then any crossed by the trades those orders make
+    async fn fire_trigger_orders(
+        &mut self,
+        market: &str,
+        fills: &[Fill],
+        redis_conn: &RedisManager,
+    ) {
+        let tick_size = match self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+        {
+            Some(orderbook) => orderbook.tick_size,
+            None => return,
+        };
+
+        let mut trade_prices: Vec<Decimal> = fills.iter().map(|fill| fill.price).collect();
+
+        while !trade_prices.is_empty() {
+            // a circuit breaker halt leaves the remaining trigger orders waiting
+            if self.trading_state(market) == TradingState::HALTED {
+                return;
+            }
+
+            let trigger_book = match self.trigger_books.get_mut(market) {
+                Some(trigger_book) => trigger_book,
+                None => return,
+            };
+
+            // trades are replayed in order so a trailing stop only fires on a reversal after it has moved
+            let mut triggered_orders: Vec<Order> = Vec::new();
+            let mut trailed_orders: HashMap<String, Order> = HashMap::new();
+            for trade_price in trade_prices.drain(..) {
+                for order in trigger_book.trail_orders(trade_price, tick_size) {
+                    trailed_orders.insert(order.order_id.clone(), order);
+                }
+                triggered_orders
+                    .extend(trigger_book.take_triggered_orders(trade_price, trade_price));
+            }
+
+            for order in triggered_orders.iter() {
+                trailed_orders.remove(&order.order_id);
+            }
+            for (_, order) in trailed_orders {
+                self.update_db_orders(order, market.to_string(), dec!(0), &vec![], redis_conn)
+                    .await;
+            }
+
+            for mut order in triggered_orders {
+                println!("Triggered order {} in {}", order.order_id, market);
+
+                // the other leg of an OCO pair is cancelled first so its locked funds are free for this one
+                self.cancel_oco_legs(market, &order.user_id, &order.order_id, redis_conn)
+                    .await;
+
+                let input_order = CreateOrder {
+                    market: market.to_string(),
+                    price: order.price,
+                    quantity: order.quantity,
+                    quote_quantity: None,
+                    side: order.side.clone(),
+                    order_type: order.order_type.triggered_order_type(),
+                    user_id: order.user_id.clone(),
+                    time_in_force: order.time_in_force.clone(),
+                    expires_at: order.expires_at,
+                    post_only: order.post_only,
+                    post_only_reprice: order.post_only_reprice,
+                    trigger_price: order.trigger_price,
+                    trailing_offset: order.trailing_offset,
+                    trailing_percent: order.trailing_percent,
+                    oco_group_id: order.oco_group_id.clone(),
+                    display_quantity: order.display_quantity,
+                    self_trade_prevention: order.self_trade_prevention.clone(),
+                    pubsub_id: None,
+                };
+
+                match self
+                    .place_order(input_order, order.order_id.clone(), redis_conn)
+                    .await
+                {
+                    Ok(order_result) => {
+                        trade_prices.extend(order_result.fills.iter().map(|fill| fill.price));
+                    }
+                    Err(reason) => {
+                        println!("Triggered order {} failed - {}", order.order_id, reason);
+
+                        order.order_status = OrderStatus::Cancelled;
+                        self.update_db_orders(
+                            order,
+                            market.to_string(),
+                            dec!(0),
+                            &vec![],
+                            redis_conn,
+                        )
+                        .await;
+                    }
+                }
+            }
+        }
+    }
+
+    // halts the market for the configured time when the fills moved prices past the circuit breaker
+    async fn check_circuit_breaker(
+        &mut self,
+        market: &str,
+        fills: &[Fill],
+        redis_conn: &RedisManager,
+    ) {
+        if fills.is_empty() {
+            return;
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let orderbook = match self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == market)
+        {
+            Some(orderbook) => orderbook,
+            None => return,
+        };
+
+        let (move_percent, halted_until) = match orderbook.record_trades(fills, now) {
+            Some(tripped) => tripped,
+            None => return,
+        };
+
+        orderbook.config.trading_state = TradingState::HALTED;
+        orderbook.halted_until = Some(halted_until);
+        let market_config = orderbook.config.clone();
+        let last_trade_price = orderbook.last_trade_price.unwrap_or(dec!(0));
+        println!(
+            "Circuit breaker tripped in {} - price moved {}%, halted until {}",
+            market, move_percent, halted_until
+        );
+
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+        self.publish_ws_circuit_breaker(
+            market.to_string(),
+            last_trade_price,
+            move_percent,
+            halted_until,
+            redis_conn,
+        )
+        .await;
+        self.publish_ws_trading_state(market.to_string(), &TradingState::HALTED, redis_conn)
+            .await;
+    }
+
+    // reopens markets whose circuit breaker halt has run out, through an auction
+    pub async fn resume_halted_markets(&mut self, redis_conn: &RedisManager) {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let mut resumed_markets: Vec<MarketConfig> = Vec::new();
+        for orderbook in self.orderbooks.iter_mut() {
+            match orderbook.halted_until {
+                Some(halted_until) if halted_until <= now => {
+                    orderbook.halted_until = None;
+                    orderbook.config.trading_state = TradingState::AUCTION;
+                    orderbook.auction_until = Some(now + self.auction_secs * 1000);
+                    resumed_markets.push(orderbook.config.clone());
+                }
+                _ => {}
+            }
+        }
+
+        if resumed_markets.is_empty() {
+            return;
+        }
+
+        for market_config in resumed_markets.iter() {
+            println!(
+                "Circuit breaker halt ended in {} - reopening with an auction",
+                market_config.symbol
+            );
+
+            self.update_db_market(market_config, redis_conn).await;
+            self.publish_ws_trading_state(
+                market_config.symbol.clone(),
+                &market_config.trading_state,
+                redis_conn,
+            )
+            .await;
+        }
+        self.publish_ws_markets_update(redis_conn).await;
+    }
+
+    // publishes the indicative price and volume of running auctions, and uncrosses those whose time is up
+    pub async fn run_auctions(&mut self, redis_conn: &RedisManager) {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let mut ended_auctions: Vec<String> = Vec::new();
+        let mut indicative_uncrosses = Vec::new(); // (market, indicative price and volume, auction end)
+        for orderbook in self
+            .orderbooks
+            .iter()
+            .filter(|orderbook| orderbook.config.trading_state == TradingState::AUCTION)
+        {
+            match orderbook.auction_until {
+                Some(auction_until) if auction_until > now => {
+                    indicative_uncrosses.push((
+                        orderbook.ticker(),
+                        orderbook.indicative_uncross(),
+                        auction_until,
+                    ));
+                }
+                _ => ended_auctions.push(orderbook.ticker()),
+            }
+        }
+
+        for (market, indicative_uncross, auction_until) in indicative_uncrosses {
+            self.publish_ws_auction(market, indicative_uncross, auction_until, redis_conn)
+                .await;
+        }
+
+        for market in ended_auctions {
+            let fills = self.uncross_auction(&market, redis_conn).await;
+
+            let orderbook = match self
+                .orderbooks
+                .iter_mut()
+                .find(|orderbook| orderbook.ticker() == market)
+            {
+                Some(orderbook) => orderbook,
+                None => continue,
+            };
+            orderbook.auction_until = None;
+            orderbook.config.trading_state = TradingState::OPEN;
+            let market_config = orderbook.config.clone();
+            println!("Auction ended in {}", market);
+
+            self.update_db_market(&market_config, redis_conn).await;
+            self.publish_ws_markets_update(redis_conn).await;
+            self.publish_ws_trading_state(market.clone(), &TradingState::OPEN, redis_conn)
+                .await;
+
+            self.fire_trigger_orders(&market, &fills, redis_conn).await;
+        }
+    }
+
+    // executes every crossing order of an auction at the clearing price, settles the trades and returns
+    // them as fills. buyers locked funds at their own price, so whatever they bid above it is released.
+    // orders self-trade prevention cancelled or decremented are released and published as in continuous trading
+    async fn uncross_auction(&mut self, market: &str, redis_conn: &RedisManager) -> Vec<Fill> {
+        let orderbook = match self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == market)
+        {
+            Some(orderbook) => orderbook,
+            None => return vec![],
+        };
+        let asset_pair = orderbook.asset_pair.clone();
+        let (trades, cancelled_orders) = orderbook.uncross();
+        if let Some(trade) = trades.first() {
+            println!(
+                "Auction in {} uncrossed {} trades at {}",
+                market,
+                trades.len(),
+                trade.price
+            );
+        }
+
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let mut fills: Vec<Fill> = Vec::new();
+        let mut orders: Vec<Order> = Vec::new(); // last state of every order that traded
+        for trade in trades.iter() {
+            // the buyer is recorded as the taker, the seller as the other side
+            let mut fill = Fill {
+                price: trade.price,
+                quantity: trade.quantity,
+                trade_id: trade.trade_id,
+                other_user_id: trade.sell_order.user_id.clone(),
+                order_id: trade.sell_order.order_id.clone(),
+                taker_fee: dec!(0),
+                maker_fee: dec!(0),
+            };
+            let settled = self
+                .settle_trade(
+                    market,
+                    None,
+                    (&trade.buy_order.user_id, &trade.buy_order.order_id),
+                    (&trade.sell_order.user_id, &trade.sell_order.order_id),
+                    &fill,
+                    redis_conn,
+                )
+                .await;
+
+            for order in [&trade.buy_order, &trade.sell_order] {
+                self.release_surplus(&order.order_id, open_funds(order), redis_conn)
+                    .await;
+                orders.retain(|traded_order| traded_order.order_id != order.order_id);
+                orders.push(order.clone());
+            }
+
+            // a trade the ledger rejected is logged by settle_trade and never persisted or published
+            match settled {
+                Ok(fees) => (fill.taker_fee, fill.maker_fee) = fees,
+                Err(_) => continue,
+            }
+            self.create_db_trades(
+                trade.buy_order.user_id.clone(),
+                market.to_string(),
+                OrderSide::BUY,
+                asset_pair.base.to_string(),
+                asset_pair.quote.to_string(),
+                &vec![fill.clone()],
+                redis_conn,
+            )
+            .await;
+            self.publish_ws_trades(
+                market.to_string(),
+                trade.buy_order.user_id.clone(),
+                &vec![fill.clone()],
+                timestamp,
+                redis_conn,
+            )
+            .await;
+            fills.push(fill);
+        }
+
+        for order in orders.iter() {
+            self.update_db_orders(
+                order.clone(),
+                market.to_string(),
+                dec!(0),
+                &vec![],
+                redis_conn,
+            )
+            .await;
+            self.publish_ws_order_update(market.to_string(), order, redis_conn)
+                .await;
+            self.cancel_oco_legs(market, &order.user_id, &order.order_id, redis_conn)
+                .await;
+        }
+
+        let mut bid_prices: Vec<Decimal> =
+            trades.iter().map(|trade| trade.buy_order.price).collect();
+        let mut ask_prices: Vec<Decimal> =
+            trades.iter().map(|trade| trade.sell_order.price).collect();
+        bid_prices.dedup();
+        ask_prices.dedup();
+        self.publish_ws_depth_levels(market.to_string(), &bid_prices, &ask_prices, redis_conn)
+            .await;
+
+        // after the trades, so an order that traded before it was cancelled is persisted as cancelled
+        for (order, cancelled_quantity) in cancelled_orders {
+            self.cancel_self_trade(market, order, cancelled_quantity, redis_conn)
+                .await;
+        }
+
+        fills
+    }
+
+    // cancels GTD orders whose expiry has passed and releases their locked funds
+    pub async fn expire_orders(&mut self, redis_conn: &RedisManager) {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let mut expired_orders: Vec<(String, AssetPair, Order)> = Vec::new();
+        // halted markets are frozen, their orders expire once trading resumes
+        for orderbook in self
+            .orderbooks
+            .iter_mut()
+            .filter(|orderbook| orderbook.config.trading_state != TradingState::HALTED)
+        {
+            for order in orderbook.remove_expired_orders(now) {
+                expired_orders.push((orderbook.ticker(), orderbook.asset_pair.clone(), order));
+            }
+        }
+
+        for (market, asset_pair, order) in expired_orders {
+            println!("Expiring order {} in {}", order.order_id, market);
+
+            self.cancel_oco_legs(&market, &order.user_id, &order.order_id, redis_conn)
+                .await;
+            self.cancel_resting_order(market, asset_pair, order, redis_conn)
+                .await;
+        }
+    }
+
+    // settles an order already taken off the book - releases what is still locked for it, persists and publishes
+    // the cancel and the depth change. returns the asset and amount released
+    async fn cancel_resting_order(
+        &mut self,
+        market: String,
+        asset_pair: AssetPair,
+        mut order: Order,
+        redis_conn: &RedisManager,
+    ) -> (String, Decimal) {
+        let asset = match order.side {
+            OrderSide::BUY => asset_pair.quote,
+            OrderSide::SELL => asset_pair.base,
+        };
+        let amount = self
+            .release_surplus(&order.order_id, dec!(0), redis_conn)
+            .await;
+
+        order.order_status = OrderStatus::Cancelled;
+        self.update_db_orders(order.clone(), market.clone(), dec!(0), &vec![], redis_conn)
+            .await;
+        self.publish_ws_order_update(market.clone(), &order, redis_conn)
+            .await;
+
+        self.publish_ws_depth_updates(market, order.price, order.side, &vec![], redis_conn)
+            .await;
+
+        (asset.to_string(), amount)
+    }
+
+    pub fn get_open_order(&mut self, open_order: GetOpenOrder) -> Result<&Order, ()> {
+        // trigger orders that have not fired yet are only in the trigger book
+        if let Some(order) = self
+            .trigger_books
+            .get(&open_order.market)
+            .and_then(|trigger_book| {
+                trigger_book.get_open_order(&open_order.user_id, &open_order.order_id)
+            })
+        {
+            return Ok(order);
+        }
+
+        let orderbook = match self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == open_order.market)
+        {
+            Some(ob) => ob,
+            None => {
+                eprintln!(
+                    "No matching orderbook found for market: {}",
+                    open_order.market
+                );
+                return Err(());
+            }
+        };
+
+        let open_order = orderbook.get_open_order(open_order.user_id, open_order.order_id);
+
+        open_order
+    }
+
+    pub async fn cancel_order(
+        &mut self,
+        cancel_order: CancelOrder,
+        redis_conn: &RedisManager,
+    ) -> Result<String, &str> {
+        self.check_market_cancellable(&cancel_order.market)?;
+
+        let market = cancel_order.market.clone();
+        let user_id = cancel_order.user_id.clone();
+
+        if let Some(trigger_book) = self.trigger_books.get_mut(&cancel_order.market) {
+            // trigger orders have nothing locked until they fire, so there is nothing to release
+            if let Some(mut order) =
+                trigger_book.cancel_order(&cancel_order.user_id, &cancel_order.order_id)
+            {
+                order.order_status = OrderStatus::Cancelled;
+                self.update_db_orders(order.clone(), market.clone(), dec!(0), &vec![], redis_conn)
+                    .await;
+                self.cancel_oco_legs(&market, &user_id, &order.order_id, redis_conn)
+                    .await;
+                return Ok(order.order_id);
+            }
+        }
+
+        let orderbook = match self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == cancel_order.market)
+        {
+            Some(ob) => ob,
+            None => {
+                eprintln!(
+                    "No matching orderbook found for market: {}",
+                    cancel_order.market
+                );
+                return Err("No matching orderbook found");
+            }
+        };
+
+        let asset_pair = orderbook.asset_pair.clone();
+        let cancel_order_id = cancel_order.order_id.clone();
+
+        let result = orderbook.cancel_order(&cancel_order.user_id, &cancel_order.order_id);
+
+        match result {
+            Ok(order) => {
+                self.cancel_resting_order(market.clone(), asset_pair, order, redis_conn)
+                    .await;
+
+                self.cancel_oco_legs(&market, &user_id, &cancel_order_id, redis_conn)
+                    .await;
+
+                return Ok(cancel_order_id);
+            }
+
+            Err(()) => {
+                println!("Failed to cancel order");
+                return Err("Failed to cancel order");
+            }
+        }
+    }
+
+    // amends a resting order in place - the funds lock is topped up before the book changes and
+    // any surplus is released after, so a failed lock leaves the order untouched
+    pub async fn modify_order(
+        &mut self,
+        modify_order: ModifyOrder,
+        redis_conn: &RedisManager,
+    ) -> Result<Order, &'static str> {
+        self.check_market_trading(&modify_order.market)?;
+
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == modify_order.market)
+            .ok_or("No matching orderbook found")?;
+
+        let order = orderbook
+            .get_open_order(modify_order.user_id.clone(), modify_order.order_id.clone())
+            .map_err(|_| "Order not found")?
+            .clone();
+
+        let price = modify_order.price.unwrap_or(order.price);
+        let quantity = modify_order.quantity.unwrap_or(order.quantity);
+
+        orderbook.check_filters(Some(price), quantity)?;
+
+        if quantity <= order.filled_quantity {
+            return Err("Quantity must be above the filled quantity");
+        }
+
+        // a modified order only ever rests - crossing prices need a new order, except in an auction
+        if price != order.price && orderbook.config.trading_state != TradingState::AUCTION {
+            let crosses = match order.side {
+                OrderSide::BUY => orderbook
+                    .best_ask()
+                    .is_some_and(|best_ask| price >= best_ask),
+                OrderSide::SELL => orderbook
+                    .best_bid()
+                    .is_some_and(|best_bid| price <= best_bid),
+            };
+
+            if crosses {
+                return Err("Modified price would cross the book");
+            }
+        }
+
+        let asset = match order.side {
+            OrderSide::BUY => orderbook.asset_pair.quote.to_string(),
+            OrderSide::SELL => orderbook.asset_pair.base.to_string(),
+        };
+        let locked_funds = open_funds(&order);
+        let new_locked_funds = open_funds(&Order {
+            price,
+            quantity,
+            ..order.clone()
+        });
+
+        if new_locked_funds > locked_funds {
+            self.lock_additional_funds(
+                &order.order_id,
+                &order.user_id,
+                &asset,
+                new_locked_funds - locked_funds,
+                redis_conn,
+            )
+            .await?;
+        }
+
+        let orderbook = self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == modify_order.market)
+            .ok_or("No matching orderbook found")?;
+
+        let modified_order = match orderbook.modify_order(
+            &modify_order.user_id,
+            &modify_order.order_id,
+            price,
+            quantity,
+        ) {
+            Ok(modified_order) => modified_order,
+            Err(reason) => {
+                self.release_surplus(&order.order_id, locked_funds, redis_conn)
+                    .await;
+
+                return Err(reason);
+            }
+        };
+
+        self.release_surplus(&order.order_id, new_locked_funds, redis_conn)
+            .await;
+
+        self.update_db_orders(
+            modified_order.clone(),
+            modify_order.market.clone(),
+            dec!(0),
+            &vec![],
+            redis_conn,
+        )
+        .await;
+        self.publish_ws_order_update(modify_order.market.clone(), &modified_order, redis_conn)
+            .await;
+
+        // both the level the order left and the one it joined change
+        if price != order.price {
+            self.publish_ws_depth_updates(
+                modify_order.market.clone(),
+                order.price,
+                order.side.clone(),
+                &vec![],
+                redis_conn,
+            )
+            .await;
+        }
+        self.publish_ws_depth_updates(
+            modify_order.market.clone(),
+            price,
+            order.side.clone(),
+            &vec![],
+            redis_conn,
+        )
+        .await;
+
+        Ok(modified_order)
+    }
+
+    pub fn get_open_orders(&mut self, open_orders: GetOpenOrders) -> Vec<&Order> {
         let orderbook = match self
             .orderbooks
             .iter_mut()
@@ -352,89 +1792,513 @@ impl Engine {
             }
         };
 
-        let open_orders: Vec<&Order> = orderbook.get_open_orders(open_orders.user_id);
+        let trigger_book = self.trigger_books.get(&open_orders.market);
+        let user_id = open_orders.user_id.clone();
+        let mut open_orders: Vec<&Order> = orderbook.get_open_orders(open_orders.user_id);
+
+        if let Some(trigger_book) = trigger_book {
+            open_orders.extend(trigger_book.get_open_orders(&user_id));
+        }
 
         open_orders
     }
 
-    pub fn cancel_all_orders(
+    pub async fn cancel_all_orders(
         &mut self,
         cancel_all_orders: CancelAllOrders,
-    ) -> Result<String, &str> {
-        let orderbook = match self
+        redis_conn: &RedisManager,
+    ) -> Result<Vec<CancelledOrder>, &'static str> {
+        let mut markets = cancel_all_orders.markets.clone();
+        markets.extend(cancel_all_orders.market.clone());
+        if markets.is_empty() {
+            // halted markets are skipped rather than failing the whole request
+            markets = self
+                .orderbooks
+                .iter()
+                .filter(|orderbook| orderbook.config.trading_state != TradingState::HALTED)
+                .map(|orderbook| orderbook.ticker())
+                .collect();
+        }
+
+        // check every market up front so a bad one does not leave the rest half cancelled
+        for market in markets.iter() {
+            if let Err(e) = self.check_market_cancellable(market) {
+                eprintln!("Cannot cancel orders in market {}: {}", market, e);
+                return Err(e);
+            }
+        }
+
+        Ok(self
+            .cancel_orders_in_markets(
+                &cancel_all_orders.user_id,
+                markets,
+                cancel_all_orders.side.as_ref(),
+                redis_conn,
+            )
+            .await)
+    }
+
+    // cancels the user's orders in the given markets without checking their trading state
+    async fn cancel_orders_in_markets(
+        &mut self,
+        user_id: &str,
+        markets: Vec<String>,
+        side: Option<&OrderSide>,
+        redis_conn: &RedisManager,
+    ) -> Vec<CancelledOrder> {
+        let mut cancelled_orders: Vec<CancelledOrder> = Vec::new();
+
+        for market in markets {
+            // trigger orders have nothing locked until they fire
+            let trigger_orders = match self.trigger_books.get_mut(&market) {
+                Some(trigger_book) => trigger_book.cancel_all_orders(user_id, side),
+                None => vec![],
+            };
+            for mut order in trigger_orders {
+                order.order_status = OrderStatus::Cancelled;
+                self.update_db_orders(order.clone(), market.clone(), dec!(0), &vec![], redis_conn)
+                    .await;
+                self.publish_ws_order_update(market.clone(), &order, redis_conn)
+                    .await;
+
+                cancelled_orders.push(CancelledOrder {
+                    market: market.clone(),
+                    order,
+                    released_asset: String::new(),
+                    released_amount: dec!(0),
+                });
+            }
+
+            let orderbook = match self
+                .orderbooks
+                .iter_mut()
+                .find(|orderbook| orderbook.ticker() == market)
+            {
+                Some(orderbook) => orderbook,
+                None => continue,
+            };
+            let asset_pair = orderbook.asset_pair.clone();
+
+            for order in orderbook.cancel_all_orders(user_id, side) {
+                let (released_asset, released_amount) = self
+                    .cancel_resting_order(
+                        market.clone(),
+                        asset_pair.clone(),
+                        order.clone(),
+                        redis_conn,
+                    )
+                    .await;
+
+                cancelled_orders.push(CancelledOrder {
+                    market: market.clone(),
+                    order: Order {
+                        order_status: OrderStatus::Cancelled,
+                        ..order
+                    },
+                    released_asset,
+                    released_amount,
+                });
+            }
+        }
+
+        // OCO legs left on the other side of a side filter go too
+        for cancelled_order in cancelled_orders.clone() {
+            self.cancel_oco_legs(
+                &cancelled_order.market,
+                user_id,
+                &cancelled_order.order.order_id,
+                redis_conn,
+            )
+            .await;
+        }
+
+        cancelled_orders
+    }
+
+    pub async fn add_market(
+        &mut self,
+        add_market: AddMarket,
+        redis_conn: &RedisManager,
+    ) -> Result<MarketConfig, &'static str> {
+        let mut market_config = add_market.market;
+
+        if self
             .orderbooks
-            .iter_mut()
-            .find(|orderbook| orderbook.ticker() == cancel_all_orders.market)
+            .iter()
+            .any(|orderbook| orderbook.config.symbol == market_config.symbol)
         {
-            Some(ob) => ob,
-            None => {
-                eprintln!(
-                    "No matching orderbook found for market: {}",
-                    cancel_all_orders.market
+            return Err("Market already exists");
+        }
+
+        if market_config.symbol
+            != format!("{}_{}", market_config.base_asset, market_config.quote_asset)
+        {
+            return Err("Market symbol must be BASE_QUOTE");
+        }
+
+        let base_asset = self
+            .assets
+            .get(&market_config.base_asset.0)
+            .ok_or("Unsupported base asset")?;
+        let quote_asset = self
+            .assets
+            .get(&market_config.quote_asset.0)
+            .ok_or("Unsupported quote asset")?;
+
+        if market_config.quantity_precision > base_asset.decimals {
+            return Err("Quantity precision is finer than the base asset's decimals");
+        }
+        if market_config.price_precision > quote_asset.decimals {
+            return Err("Price precision is finer than the quote asset's decimals");
+        }
+
+        if market_config.min_order_size <= dec!(0)
+            || market_config.min_order_size > market_config.max_order_size
+        {
+            return Err("Invalid order size limits");
+        }
+
+        Self::validate_price_protection(&market_config)?;
+
+        for fee_rate in [market_config.maker_fee_rate, market_config.taker_fee_rate] {
+            if fee_rate < dec!(0) || fee_rate >= dec!(1) {
+                return Err("Fee rates must be at least 0 and below 1");
+            }
+        }
+
+        // a new market always starts trading - pause it afterwards if needed - and opens with an auction
+        market_config.status = MarketStatus::TRADING;
+        market_config.trading_state = TradingState::AUCTION;
+
+        let mut orderbook = OrderBook::new(market_config.clone(), 1);
+        orderbook.auction_until =
+            Some(chrono::Utc::now().timestamp_millis() + self.auction_secs * 1000);
+        self.orderbooks.push(orderbook);
+        self.trigger_books
+            .insert(market_config.symbol.clone(), TriggerBook::new());
+        println!("✓ Added orderbook for {}", market_config.symbol);
+
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+
+        Ok(market_config)
+    }
+
+    // pauses, resumes, delists or relists a market. delisting cancels every order left in it
+    pub async fn update_market_status(
+        &mut self,
+        update_market_status: UpdateMarketStatus,
+        redis_conn: &RedisManager,
+    ) -> Result<MarketConfig, &'static str> {
+        let symbol = update_market_status.symbol.clone();
+
+        let orderbook = self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.config.symbol == symbol)
+            .ok_or("No matching orderbook found")?;
+        orderbook.config.status = update_market_status.status.clone();
+
+        if let MarketStatus::DELISTED = update_market_status.status {
+            let mut user_ids: Vec<String> = orderbook
+                .bids
+                .values()
+                .chain(orderbook.asks.values())
+                .flatten()
+                .map(|order| order.user_id.clone())
+                .collect();
+            if let Some(trigger_book) = self.trigger_books.get(&symbol) {
+                user_ids.extend(
+                    trigger_book
+                        .orders
+                        .iter()
+                        .map(|order| order.user_id.clone()),
                 );
-                return Err("No matching orderbook found");
             }
+            user_ids.sort();
+            user_ids.dedup();
+
+            // delisting goes ahead whatever the trading state
+            for user_id in user_ids {
+                self.cancel_orders_in_markets(&user_id, vec![symbol.clone()], None, redis_conn)
+                    .await;
+            }
+        }
+
+        let market_config = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.config.symbol == symbol)
+            .map(|orderbook| orderbook.config.clone())
+            .ok_or("No matching orderbook found")?;
+        println!(
+            "Market {} is now {:?}",
+            market_config.symbol, market_config.status
+        );
+
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+
+        Ok(market_config)
+    }
+
+    pub async fn set_trading_state(
+        &mut self,
+        set_trading_state: SetTradingState,
+        redis_conn: &RedisManager,
+    ) -> Result<MarketConfig, &'static str> {
+        let market = set_trading_state.market.clone();
+        let current_state = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .map(|orderbook| orderbook.config.trading_state.clone())
+            .ok_or("No matching orderbook found")?;
+
+        // a halted market reopens through an auction so the first order in does not set the price
+        let trading_state = match (&current_state, set_trading_state.trading_state) {
+            (TradingState::HALTED, TradingState::OPEN | TradingState::POST_ONLY) => {
+                TradingState::AUCTION
+            }
+            (_, trading_state) => trading_state,
         };
 
-        let assets: Vec<&str> = cancel_all_orders.market.split('_').collect();
-        let base_asset_str = assets[0];
-        let quote_asset_str = assets[1];
-        let base_asset = Asset::from_str(base_asset_str)?;
-        let quote_asset = Asset::from_str(quote_asset_str)?;
+        // ending an auction early uncrosses the book first, unless the market is being halted
+        let mut fills: Vec<Fill> = Vec::new();
+        if current_state == TradingState::AUCTION
+            && !matches!(trading_state, TradingState::AUCTION | TradingState::HALTED)
+        {
+            fills = self.uncross_auction(&market, redis_conn).await;
+        }
 
-        let open_orders = orderbook.cancel_all_orders(cancel_all_orders.user_id.clone());
+        let auction_until = chrono::Utc::now().timestamp_millis() + self.auction_secs * 1000;
+        let orderbook = self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == market)
+            .ok_or("No matching orderbook found")?;
+        if trading_state != TradingState::AUCTION {
+            orderbook.auction_until = None;
+        } else if current_state != TradingState::AUCTION {
+            orderbook.auction_until = Some(auction_until);
+        }
+        orderbook.config.trading_state = trading_state;
+        // an admin decision replaces any circuit breaker halt still running
+        orderbook.halted_until = None;
+        let market_config = orderbook.config.clone();
+        println!(
+            "Market {} trading state is now {:?}",
+            market_config.symbol, market_config.trading_state
+        );
 
-        let mut balance_updates: Vec<(String, Asset, Decimal, AmountType)> = Vec::new();
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+        self.publish_ws_trading_state(
+            market_config.symbol.clone(),
+            &market_config.trading_state,
+            redis_conn,
+        )
+        .await;
+
+        // stop orders only fire into a market that matches freely
+        if market_config.trading_state == TradingState::OPEN {
+            self.fire_trigger_orders(&market, &fills, redis_conn).await;
+        }
 
-        for order in open_orders {
-            let quantity = match order.side {
-                OrderSide::BUY => (order.quantity - order.filled_quantity) * order.price,
-                OrderSide::SELL => order.quantity - order.filled_quantity,
-            };
+        Ok(market_config)
+    }
 
-            match order.side {
-                OrderSide::BUY => {
-                    balance_updates.push((
-                        order.user_id.clone(),
-                        quote_asset.clone(),
-                        quantity,
-                        AmountType::AVAILABLE,
-                    ));
-                    balance_updates.push((
-                        order.user_id.clone(),
-                        quote_asset.clone(),
-                        -quantity,
-                        AmountType::LOCKED,
-                    ));
-                }
+    // band and breaker settings are percentages of the price, the breaker times are in seconds
+    fn validate_price_protection(market_config: &MarketConfig) -> Result<(), &'static str> {
+        for percent in [
+            market_config.price_band_percent,
+            market_config.circuit_breaker_percent,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if percent <= dec!(0) || percent >= dec!(100) {
+                return Err("Percentages must be between 0 and 100");
+            }
+        }
 
-                OrderSide::SELL => {
-                    balance_updates.push((
-                        order.user_id.clone(),
-                        base_asset.clone(),
-                        quantity,
-                        AmountType::AVAILABLE,
-                    ));
-                    balance_updates.push((
-                        order.user_id.clone(),
-                        base_asset.clone(),
-                        -quantity,
-                        AmountType::LOCKED,
-                    ));
-                }
+        if let Some(reference_price) = market_config.reference_price {
+            if reference_price <= dec!(0) {
+                return Err("Reference price must be positive");
+            }
+        }
+
+        for secs in [
+            market_config.circuit_breaker_window_secs,
+            market_config.circuit_breaker_halt_secs,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if secs <= 0 {
+                return Err("Circuit breaker window and halt must be positive");
             }
         }
 
-        // Perform balance updates after the loop, ensuring only one mutable borrow of `self`
-        for (user_id, asset, amount, amount_type) in balance_updates {
-            self.update_balance_with_lock(user_id, asset, amount, amount_type)?;
+        Ok(())
+    }
+
+    pub async fn set_price_protection(
+        &mut self,
+        set_price_protection: SetPriceProtection,
+        redis_conn: &RedisManager,
+    ) -> Result<MarketConfig, &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == set_price_protection.market)
+            .ok_or("No matching orderbook found")?;
+
+        let mut market_config = orderbook.config.clone();
+        market_config.price_band_percent = set_price_protection.price_band_percent;
+        market_config.reference_price = set_price_protection.reference_price;
+        market_config.circuit_breaker_percent = set_price_protection.circuit_breaker_percent;
+        market_config.circuit_breaker_window_secs =
+            set_price_protection.circuit_breaker_window_secs;
+        market_config.circuit_breaker_halt_secs = set_price_protection.circuit_breaker_halt_secs;
+        Self::validate_price_protection(&market_config)?;
+
+        orderbook.config = market_config.clone();
+        println!(
+            "Market {} price band is now {:?}%, circuit breaker {:?}%",
+            market_config.symbol,
+            market_config.price_band_percent,
+            market_config.circuit_breaker_percent
+        );
+
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+
+        Ok(market_config)
+    }
+
+    // switches how the market's price levels share out incoming orders - resting orders keep their places
+    pub async fn set_matching_policy(
+        &mut self,
+        set_matching_policy: SetMatchingPolicy,
+        redis_conn: &RedisManager,
+    ) -> Result<MarketConfig, &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter_mut()
+            .find(|orderbook| orderbook.ticker() == set_matching_policy.market)
+            .ok_or("No matching orderbook found")?;
+        orderbook.config.matching_policy = set_matching_policy.matching_policy;
+        let market_config = orderbook.config.clone();
+        println!(
+            "Market {} matching policy is now {:?}",
+            market_config.symbol, market_config.matching_policy
+        );
+
+        self.update_db_market(&market_config, redis_conn).await;
+        self.publish_ws_markets_update(redis_conn).await;
+
+        Ok(market_config)
+    }
+
+    // lists a new asset or changes the status of a listed one - decimals are fixed once listed
+    pub async fn upsert_asset(
+        &mut self,
+        upsert_asset: UpsertAsset,
+        redis_conn: &RedisManager,
+    ) -> Result<AssetConfig, &'static str> {
+        let asset_config = upsert_asset.asset;
+
+        if asset_config.symbol.is_empty()
+            || !asset_config
+                .symbol
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            return Err("Asset symbol must be uppercase letters and digits");
+        }
+
+        if asset_config.decimals > 28 {
+            return Err("Asset decimals must be at most 28");
+        }
+
+        if let Some(existing) = self.assets.get(&asset_config.symbol) {
+            if existing.decimals != asset_config.decimals {
+                return Err("Asset decimals cannot be changed");
+            }
         }
 
-        // Return a success message after cancelling all orders
-        Ok(format!(
-            "All orders for user {} cancelled successfully",
-            cancel_all_orders.user_id
-        ))
+        self.assets
+            .insert(asset_config.symbol.clone(), asset_config.clone());
+        println!(
+            "Asset {} is now {:?}",
+            asset_config.symbol, asset_config.status
+        );
+
+        self.update_db_asset(&asset_config, redis_conn).await;
+
+        Ok(asset_config)
+    }
+
+    pub fn get_assets(&self) -> Vec<&AssetConfig> {
+        let mut assets: Vec<&AssetConfig> = self.assets.values().collect();
+        assets.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        assets
+    }
+
+    // the filters of every market, with the tick size derived from its price precision
+    pub fn get_exchange_info(&self) -> Vec<serde_json::Value> {
+        self.orderbooks
+            .iter()
+            .map(|orderbook| {
+                serde_json::json!({
+                    "symbol": orderbook.config.symbol,
+                    "base_asset": orderbook.config.base_asset,
+                    "quote_asset": orderbook.config.quote_asset,
+                    "min_order_size": orderbook.config.min_order_size,
+                    "max_order_size": orderbook.config.max_order_size,
+                    "price_precision": orderbook.config.price_precision,
+                    "quantity_precision": orderbook.config.quantity_precision,
+                    "tick_size": orderbook.tick_size,
+                    "status": orderbook.config.status,
+                    "trading_state": orderbook.config.trading_state,
+                    "matching_policy": orderbook.config.matching_policy,
+                    "maker_fee_rate": orderbook.config.maker_fee_rate,
+                    "taker_fee_rate": orderbook.config.taker_fee_rate,
+                })
+            })
+            .collect()
+    }
+
+    // the user's tier, its 30-day volume and the rates it pays on every market after the tier discounts
+    pub fn get_fee_tier(&self, get_fee_tier: GetFeeTier) -> serde_json::Value {
+        let now = chrono::Utc::now().timestamp_millis();
+        let user_id = get_fee_tier.user_id;
+
+        let markets: Vec<serde_json::Value> = self
+            .orderbooks
+            .iter()
+            .map(|orderbook| {
+                let (maker_fee_rate, taker_fee_rate) = self.fees.rates(
+                    &user_id,
+                    orderbook.config.maker_fee_rate,
+                    orderbook.config.taker_fee_rate,
+                    now,
+                );
+                serde_json::json!({
+                    "symbol": orderbook.config.symbol,
+                    "maker_fee_rate": maker_fee_rate,
+                    "taker_fee_rate": taker_fee_rate,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "user_id": user_id,
+            "tier": self.fees.tier(&user_id, now),
+            "volume_30d": self.fees.volume(&user_id, now),
+            "markets": markets,
+        })
     }
 
     pub fn get_depth(&self, depth: GetDepth) -> (Vec<(Decimal, Decimal)>, Vec<(Decimal, Decimal)>) {
@@ -455,157 +2319,591 @@ impl Engine {
         depth
     }
 
-    pub async fn check_and_lock_funds(&mut self, order: &CreateOrder) -> Result<(), &str> {
-        let assets: Vec<&str> = order.market.split('_').collect();
-        let base_asset_str = assets[0];
-        let quote_asset_str = assets[1];
+    // reserves the funds needed for the order under its id (quote asset for buys, base asset for sells)
+    pub async fn check_and_lock_funds(
+        &mut self,
+        order: &CreateOrder,
+        order_id: &str,
+        redis_conn: &RedisManager,
+    ) -> Result<(), &'static str> {
+        let asset_pair = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == order.market)
+            .map(|orderbook| orderbook.asset_pair.clone())
+            .ok_or("No matching orderbook found")?;
 
-        // Convert string assets to Asset enum
-        let _base_asset = Asset::from_str(base_asset_str)?;
-        let _quote_asset = Asset::from_str(quote_asset_str)?;
+        // both assets must still be listed and active in the registry
+        self.check_asset_active(&asset_pair.base)?;
+        self.check_asset_active(&asset_pair.quote)?;
 
         let user_id = &order.user_id;
 
         match order.side {
             OrderSide::BUY => {
-                // For buy orders, check if user has enough quote asset (e.g., USDC)
-                let balance_info = self.user_service_client
-                    .get_balance(user_id, quote_asset_str)
-                    .await
-                    .map_err(|_| "Failed to get balance from user service")?;
-
-                let total_cost = (order.price * order.quantity).to_f64().unwrap();
-                if balance_info.available >= total_cost {
-                    // Lock the funds in user service
-                    self.user_service_client
-                        .lock_funds(user_id, quote_asset_str, total_cost)
-                        .await
-                        .map_err(|_| "Failed to lock funds in user service")?;
-                } else {
-                    return Err("Insufficient funds");
+                // market buys have no meaningful price, so lock what their fills would cost
+                let total_cost = match order.order_type {
+                    OrderType::MARKET => {
+                        let orderbook = self
+                            .orderbooks
+                            .iter()
+                            .find(|orderbook| orderbook.ticker() == order.market)
+                            .ok_or("No matching orderbook found")?;
+
+                        orderbook.market_buy_cost(&Self::build_order(order, order_id.to_string()))
+                    }
+                    _ => order.price * order.quantity,
+                };
+
+                if total_cost <= dec!(0) {
+                    return Err("No liquidity available for market order");
                 }
+
+                // For buy orders, check if user has enough quote asset (e.g., USDC)
+                let change = self.ledger.reserve(
+                    order_id,
+                    user_id,
+                    &asset_pair.quote.to_string(),
+                    total_cost,
+                )?;
+                self.update_db_balance(&change, redis_conn).await;
+
+                Ok(())
             }
 
             OrderSide::SELL => {
                 // For sell orders, check if user has enough base asset (e.g., SOL)
-                let balance_info = self.user_service_client
-                    .get_balance(user_id, base_asset_str)
-                    .await
-                    .map_err(|_| "Failed to get balance from user service")?;
-
-                let quantity = order.quantity.to_f64().unwrap();
-                if balance_info.available >= quantity {
-                    // Lock the funds in user service
-                    self.user_service_client
-                        .lock_funds(user_id, base_asset_str, quantity)
-                        .await
-                        .map_err(|_| "Failed to lock funds in user service")?;
-                } else {
-                    return Err("Insufficient asset quantity");
-                }
+                let change = self
+                    .ledger
+                    .reserve(
+                        order_id,
+                        user_id,
+                        &asset_pair.base.to_string(),
+                        order.quantity,
+                    )
+                    .map_err(|_| "Insufficient asset quantity")?;
+                self.update_db_balance(&change, redis_conn).await;
+
+                Ok(())
             }
         }
+    }
+
+    // locks `amount` more of an asset for an order that already holds a lock, e.g. one being modified
+    pub async fn lock_additional_funds(
+        &mut self,
+        order_id: &str,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+        redis_conn: &RedisManager,
+    ) -> Result<(), &'static str> {
+        let change = self.ledger.reserve(order_id, user_id, asset, amount)?;
+        self.update_db_balance(&change, redis_conn).await;
 
         Ok(())
     }
 
-    pub async fn update_user_balance(
+    // unlocks what an order reserved beyond `needed` - the price improvement of a buy that filled below its
+    // limit, or everything once the order is closed. returns the amount released
+    async fn release_surplus(
         &mut self,
-        base_asset: Asset,
-        quote_asset: Asset,
-        order: Order,
-        order_result: &ProcessOrderResult,
-    ) -> Result<(), &str> {
-        let base_asset_str = base_asset.to_string();
-        let quote_asset_str = quote_asset.to_string();
+        order_id: &str,
+        needed: Decimal,
+        redis_conn: &RedisManager,
+    ) -> Decimal {
+        match self.ledger.release_surplus(order_id, needed) {
+            Ok(Some(change)) => {
+                self.update_db_balance(&change, redis_conn).await;
+                -change.locked_change
+            }
+            Ok(None) => dec!(0),
+            Err(e) => {
+                println!("Failed to release funds for order {} - {}", order_id, e);
+                dec!(0)
+            }
+        }
+    }
+
+    // moves a trade between the two sides - the buyer pays out of the quote it locked and the seller out of
+    // the base it locked. each is credited what the other paid less its fee, which goes to the fee account.
+    // the taker pays the taker rate and the other side the maker rate - without a taker, as in an auction,
+    // both pay the maker rate. `buyer` and `seller` are (user id, order id). returns the buyer's fee in base
+    // and the seller's fee in quote, or why the ledger rejected the trade - then no balance moved at all
+    // buyer and seller of a fill as user and order id - the taker is on the order's side
+    fn trade_sides<'a>(
+        order: &'a Order,
+        fill: &'a Fill,
+    ) -> ((&'a str, &'a str), (&'a str, &'a str)) {
+        let taker = (order.user_id.as_str(), order.order_id.as_str());
+        let maker = (fill.other_user_id.as_str(), fill.order_id.as_str());
 
         match order.side {
-            OrderSide::BUY => {
-                for fill in &order_result.fills {
-                    let quantity = fill.quantity.to_f64().unwrap();
-                    let total_price = (fill.price * fill.quantity).to_f64().unwrap();
-
-                    // Update buyer's balances (current user) - unlock quote asset and add base asset
-                    self.user_service_client
-                        .unlock_funds(&order.user_id, &quote_asset_str, total_price)
-                        .await
-                        .map_err(|_| "Failed to unlock buyer funds")?;
-
-                    self.user_service_client
-                        .update_balance(&order.user_id, &base_asset_str, quantity, "add")
-                        .await
-                        .map_err(|_| "Failed to update buyer balance")?;
-
-                    // Update seller's balances (other user) - unlock base asset and add quote asset
-                    self.user_service_client
-                        .unlock_funds(&fill.other_user_id, &base_asset_str, quantity)
-                        .await
-                        .map_err(|_| "Failed to unlock seller funds")?;
-
-                    self.user_service_client
-                        .update_balance(&fill.other_user_id, &quote_asset_str, total_price, "add")
-                        .await
-                        .map_err(|_| "Failed to update seller balance")?;
-                }
-            }
-            OrderSide::SELL => {
-                for fill in &order_result.fills {
-                    let quantity = fill.quantity.to_f64().unwrap();
-                    let total_price = (fill.price * fill.quantity).to_f64().unwrap();
-
-                    // Update seller's balances (current user) - unlock base asset and add quote asset
-                    self.user_service_client
-                        .unlock_funds(&order.user_id, &base_asset_str, quantity)
-                        .await
-                        .map_err(|_| "Failed to unlock seller funds")?;
-
-                    self.user_service_client
-                        .update_balance(&order.user_id, &quote_asset_str, total_price, "add")
-                        .await
-                        .map_err(|_| "Failed to update seller balance")?;
-
-                    // Update buyer's balances (other user) - unlock quote asset and add base asset
-                    self.user_service_client
-                        .unlock_funds(&fill.other_user_id, &quote_asset_str, total_price)
-                        .await
-                        .map_err(|_| "Failed to unlock buyer funds")?;
-
-                    self.user_service_client
-                        .update_balance(&fill.other_user_id, &base_asset_str, quantity, "add")
-                        .await
-                        .map_err(|_| "Failed to update buyer balance")?;
+            OrderSide::BUY => (taker, maker),
+            OrderSide::SELL => (maker, taker),
+        }
+    }
+
+    // checks the fills matching would make for `order` against the ledger, in the order they settle. fees
+    // only come out of what each side receives, so they cannot make a trade fail and are left out
+    fn check_fills_settle(&self, market: &str, order: &Order) -> Result<(), &'static str> {
+        let orderbook = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .ok_or("No matching orderbook found")?;
+
+        // auctions only collect orders, and a fill or kill order that cannot fill matches nothing
+        let order_result = orderbook.simulate(order);
+        if orderbook.config.trading_state == TradingState::AUCTION
+            || (matches!(order.time_in_force, TimeInForce::FOK)
+                && order_result.executed_quantity < order.quantity)
+        {
+            return Ok(());
+        }
+
+        let base_asset = orderbook.asset_pair.base.to_string();
+        let quote_asset = orderbook.asset_pair.quote.to_string();
+        let settlements: Vec<Settlement> = order_result
+            .fills
+            .iter()
+            .map(|fill| {
+                let ((buyer_id, buy_order_id), (seller_id, sell_order_id)) =
+                    Self::trade_sides(order, fill);
+
+                Settlement {
+                    buyer_id,
+                    buy_order_id,
+                    seller_id,
+                    sell_order_id,
+                    base_asset: &base_asset,
+                    quote_asset: &quote_asset,
+                    quantity: fill.quantity,
+                    cost: fill.price * fill.quantity,
+                    buyer_fee: dec!(0),
+                    seller_fee: dec!(0),
+                    fee_account: &self.fee_account,
                 }
+            })
+            .collect();
+
+        self.ledger.check_settlements(&settlements)
+    }
+
+    async fn settle_trade(
+        &mut self,
+        market: &str,
+        taker_side: Option<OrderSide>,
+        buyer: (&str, &str),
+        seller: (&str, &str),
+        fill: &Fill,
+        redis_conn: &RedisManager,
+    ) -> Result<(Decimal, Decimal), &'static str> {
+        let (asset_pair, maker_fee_rate, taker_fee_rate) = self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+            .map(|orderbook| {
+                (
+                    orderbook.asset_pair.clone(),
+                    orderbook.config.maker_fee_rate,
+                    orderbook.config.taker_fee_rate,
+                )
+            })
+            .ok_or("No matching orderbook found")?;
+        let (buyer_id, buy_order_id) = buyer;
+        let (seller_id, sell_order_id) = seller;
+        let base_asset = asset_pair.base.to_string();
+        let quote_asset = asset_pair.quote.to_string();
+        let cost = fill.price * fill.quantity;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let (buyer_maker_rate, buyer_taker_rate) =
+            self.fees
+                .rates(buyer_id, maker_fee_rate, taker_fee_rate, now);
+        let (seller_maker_rate, seller_taker_rate) =
+            self.fees
+                .rates(seller_id, maker_fee_rate, taker_fee_rate, now);
+        let (buyer_fee_rate, seller_fee_rate) = match taker_side {
+            Some(OrderSide::BUY) => (buyer_taker_rate, seller_maker_rate),
+            Some(OrderSide::SELL) => (buyer_maker_rate, seller_taker_rate),
+            None => (buyer_maker_rate, seller_maker_rate),
+        };
+        let buyer_fee = self.round_fee(fill.quantity * buyer_fee_rate, &base_asset);
+        let seller_fee = self.round_fee(cost * seller_fee_rate, &quote_asset);
+
+        let changes = self
+            .ledger
+            .settle(&Settlement {
+                buyer_id,
+                buy_order_id,
+                seller_id,
+                sell_order_id,
+                base_asset: &base_asset,
+                quote_asset: &quote_asset,
+                quantity: fill.quantity,
+                cost,
+                buyer_fee,
+                seller_fee,
+                fee_account: &self.fee_account,
+            })
+            .inspect_err(|e| {
+                eprintln!(
+                    "Rejected settlement of trade {} in {} between {} and {} - {}",
+                    fill.trade_id, market, buyer_id, seller_id, e
+                )
+            })?;
+        for change in changes.iter() {
+            self.update_db_settled_balance(change, redis_conn).await;
+        }
+
+        // the trade counts towards both users' tiers from the next one on
+        self.fees.record_volume(buyer_id, now, cost);
+        self.fees.record_volume(seller_id, now, cost);
+
+        Ok((buyer_fee, seller_fee))
+    }
+
+    // fees are rounded up to what the asset can represent
+    fn round_fee(&self, fee: Decimal, asset: &str) -> Decimal {
+        match self.assets.get(asset) {
+            Some(asset_config) => {
+                fee.round_dp_with_strategy(asset_config.decimals, RoundingStrategy::AwayFromZero)
             }
+            None => fee,
         }
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{engine, resting_orders};
+    use super::super::ledger::{open_funds, Balance};
+    use super::Engine;
+    use crate::types::engine::{CancelOrder, CreateOrder, ModifyOrder, OrderSide, TradingState};
+    use proptest::prelude::*;
+    use redis::RedisManager;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    const USERS: [&str; 3] = ["alice", "bob", "carol"];
+    const SELF_TRADE_PREVENTIONS: [&str; 4] = [
+        "CANCEL_NEWEST",
+        "CANCEL_OLDEST",
+        "CANCEL_BOTH",
+        "DECREMENT_AND_CANCEL",
+    ];
+
+    fn create_order(user_id: &str, side: &str, price: Decimal, quantity: Decimal) -> CreateOrder {
+        serde_json::from_value(serde_json::json!({
+            "market": "SOL_USDC",
+            "price": price,
+            "quantity": quantity,
+            "side": side,
+            "order_type": "LIMIT",
+            "user_id": user_id,
+        }))
+        .unwrap()
     }
 
-    // Helper function to update balance with lock
-    fn update_balance_with_lock(
-        &self,
-        user_id: String,
-        asset: Asset,
-        amount: Decimal,
-        amount_type: AmountType,
-    ) -> Result<(), &str> {
-        // Access the user's balance via the Mutex
-        let balances = &self.balances;
-        let user_balance_mutex = balances.get(&user_id).ok_or("No matching user found")?;
+    #[tokio::test]
+    async fn decremented_order_settles_the_rest_of_its_quantity() {
+        let redis = RedisManager::disconnected().unwrap();
+        let mut engine = engine(&USERS, dec!(100), dec!(100000));
+
+        engine
+            .create_order(create_order("alice", "SELL", dec!(100), dec!(10)), &redis)
+            .await
+            .unwrap();
+        let mut self_trade = create_order("alice", "BUY", dec!(100), dec!(5));
+        self_trade.self_trade_prevention =
+            serde_json::from_value(serde_json::json!("DECREMENT_AND_CANCEL")).unwrap();
+        let (_, result) = engine.create_order(self_trade, &redis).await.unwrap();
+        assert_eq!(result.cancelled_quantity, dec!(5));
+
+        // the sell still rests with 5, and only those are locked for it
+        assert_eq!(engine.ledger.balance("alice", "SOL").locked, dec!(5));
+        assert_eq!(engine.ledger.balance("alice", "USDC").locked, dec!(0));
+
+        let (_, result) = engine
+            .create_order(create_order("bob", "BUY", dec!(100), dec!(5)), &redis)
+            .await
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(5));
+        assert_eq!(result.fills.len(), 1);
+
+        assert_eq!(
+            engine.ledger.balance("alice", "SOL"),
+            Balance {
+                available: dec!(95),
+                locked: dec!(0),
+            }
+        );
+        // 500 for the 5 sold, less the maker fee
+        assert_eq!(
+            engine.ledger.balance("alice", "USDC").available,
+            dec!(100499.5)
+        );
+        assert!(resting_orders(&engine.orderbooks[0]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn market_buy_locks_what_self_trade_prevention_leaves_it_to_pay() {
+        let redis = RedisManager::disconnected().unwrap();
+        let mut engine = engine(&USERS, dec!(100), dec!(100000));
+
+        engine
+            .create_order(create_order("alice", "SELL", dec!(100), dec!(1)), &redis)
+            .await
+            .unwrap();
+        engine
+            .create_order(create_order("bob", "SELL", dec!(101), dec!(2)), &redis)
+            .await
+            .unwrap();
+
+        // alice's own ask is cancelled, so both lots come from bob at 101
+        let mut market_buy = create_order("alice", "BUY", dec!(0), dec!(2));
+        market_buy.order_type = serde_json::from_value(serde_json::json!("MARKET")).unwrap();
+        market_buy.self_trade_prevention =
+            serde_json::from_value(serde_json::json!("CANCEL_OLDEST")).unwrap();
+        let (_, result) = engine.create_order(market_buy, &redis).await.unwrap();
+
+        assert_eq!(result.executed_quantity, dec!(2));
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(
+            engine.ledger.balance("alice", "USDC"),
+            Balance {
+                available: dec!(99798),
+                locked: dec!(0),
+            }
+        );
+        assert!(resting_orders(&engine.orderbooks[0]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn order_that_cannot_settle_leaves_the_book_untouched() {
+        let redis = RedisManager::disconnected().unwrap();
+        let mut engine = engine(&USERS, dec!(100), dec!(100000));
+
+        let (ask_id, _) = engine
+            .create_order(create_order("bob", "SELL", dec!(100), dec!(2)), &redis)
+            .await
+            .unwrap();
+        // bob's ask only has 1 of its 2 reserved
+        engine.ledger.release_surplus(&ask_id, dec!(1)).unwrap();
+
+        let result = engine
+            .create_order(create_order("alice", "BUY", dec!(100), dec!(2)), &redis)
+            .await;
+
+        assert_eq!(result.unwrap_err(), "Insufficient reserved funds");
+        let resting = resting_orders(&engine.orderbooks[0]);
+        assert_eq!(resting.len(), 1);
+        assert_eq!(resting[0].order_id, ask_id);
+        assert_eq!(resting[0].filled_quantity, dec!(0));
+        assert_eq!(
+            engine.ledger.balance("alice", "USDC"),
+            Balance {
+                available: dec!(100000),
+                locked: dec!(0),
+            }
+        );
+        assert_eq!(engine.ledger.balance("bob", "USDC").available, dec!(100000));
+    }
+
+    #[tokio::test]
+    async fn auction_never_trades_a_user_with_itself() {
+        let redis = RedisManager::disconnected().unwrap();
+        let mut engine = engine(&USERS, dec!(100), dec!(100000));
+        engine.orderbooks[0].config.trading_state = TradingState::AUCTION;
+        engine.orderbooks[0].auction_until = Some(i64::MAX);
+
+        for user_id in ["alice", "bob"] {
+            engine
+                .create_order(create_order(user_id, "SELL", dec!(100), dec!(5)), &redis)
+                .await
+                .unwrap();
+        }
+        let mut bid = create_order("alice", "BUY", dec!(101), dec!(5));
+        bid.self_trade_prevention =
+            serde_json::from_value(serde_json::json!("CANCEL_OLDEST")).unwrap();
+        engine.create_order(bid, &redis).await.unwrap();
+
+        engine.orderbooks[0].auction_until = Some(0);
+        engine.run_auctions(&redis).await;
+
+        // alice's own ask is cancelled instead, and her bid buys bob's
+        assert!(resting_orders(&engine.orderbooks[0]).is_empty());
+        assert_eq!(
+            engine.ledger.balance("alice", "SOL"),
+            Balance {
+                available: dec!(104.995),
+                locked: dec!(0),
+            }
+        );
+        assert_eq!(
+            engine.ledger.balance("bob", "SOL"),
+            Balance {
+                available: dec!(95),
+                locked: dec!(0),
+            }
+        );
+    }
 
-        // Lock the Mutex to safely access the user's balances
-        let mut user_balance = user_balance_mutex.lock().map_err(|_| "Mutex lock failed")?;
+    #[derive(Debug, Clone)]
+    enum Action {
+        Limit(usize, bool, i64, i64, bool, usize), // user, buy, price, quantity, ioc, self-trade prevention
+        MarketBuy(usize, i64, usize),              // user, quote quantity, self-trade prevention
+        Cancel(usize),                             // index of a resting order
+        Modify(usize, i64, i64),                   // index of a resting order, price, quantity
+        Auction,                                   // start an auction, or uncross the running one
+    }
 
-        let balance = user_balance
-            .balance
-            .get_mut(&asset)
-            .ok_or("No balance for asset found")?;
+    // the three users trade both sides in a narrow price range, so their orders cross each other and
+    // their own orders
+    fn action() -> impl Strategy<Value = Action> {
+        prop_oneof![
+            6 => (0..3usize, any::<bool>(), 95..105i64, 1..10i64, any::<bool>(), 0..4usize)
+                .prop_map(|(user, buy, price, quantity, ioc, stp)| {
+                    Action::Limit(user, buy, price, quantity, ioc, stp)
+                }),
+            1 => (0..3usize, 50..2000i64, 0..4usize)
+                .prop_map(|(user, quote, stp)| Action::MarketBuy(user, quote, stp)),
+            1 => any::<usize>().prop_map(Action::Cancel),
+            1 => (any::<usize>(), 95..105i64, 1..10i64)
+                .prop_map(|(index, price, quantity)| Action::Modify(index, price, quantity)),
+            1 => Just(Action::Auction),
+        ]
+    }
 
-        match amount_type {
-            AmountType::AVAILABLE => balance.available += amount,
-            AmountType::LOCKED => balance.locked += amount,
+    async fn apply(engine: &mut Engine, action: Action, redis: &RedisManager) {
+        let resting = resting_orders(&engine.orderbooks[0]);
+
+        match action {
+            Action::Limit(user, buy, price, quantity, ioc, stp) => {
+                let side = if buy { "BUY" } else { "SELL" };
+                let mut input_order = create_order(
+                    USERS[user],
+                    side,
+                    Decimal::from(price),
+                    Decimal::from(quantity),
+                );
+                if ioc {
+                    input_order.time_in_force =
+                        serde_json::from_value(serde_json::json!("IOC")).unwrap();
+                }
+                input_order.self_trade_prevention =
+                    serde_json::from_value(serde_json::json!(SELF_TRADE_PREVENTIONS[stp])).unwrap();
+                let _ = engine.create_order(input_order, redis).await;
+            }
+            Action::MarketBuy(user, quote, stp) => {
+                let input_order: CreateOrder = serde_json::from_value(serde_json::json!({
+                    "market": "SOL_USDC",
+                    "quote_quantity": Decimal::from(quote),
+                    "side": "BUY",
+                    "order_type": "MARKET",
+                    "user_id": USERS[user],
+                    "self_trade_prevention": SELF_TRADE_PREVENTIONS[stp],
+                }))
+                .unwrap();
+                let _ = engine.create_order(input_order, redis).await;
+            }
+            Action::Cancel(index) if !resting.is_empty() => {
+                let order = &resting[index % resting.len()];
+                let _ = engine
+                    .cancel_order(
+                        CancelOrder {
+                            order_id: order.order_id.clone(),
+                            user_id: order.user_id.clone(),
+                            market: "SOL_USDC".to_string(),
+                            pubsub_id: None,
+                        },
+                        redis,
+                    )
+                    .await;
+            }
+            Action::Modify(index, price, quantity) if !resting.is_empty() => {
+                let order = &resting[index % resting.len()];
+                let _ = engine
+                    .modify_order(
+                        ModifyOrder {
+                            order_id: order.order_id.clone(),
+                            user_id: order.user_id.clone(),
+                            market: "SOL_USDC".to_string(),
+                            price: Some(Decimal::from(price)),
+                            quantity: Some(Decimal::from(quantity)),
+                            pubsub_id: None,
+                        },
+                        redis,
+                    )
+                    .await;
+            }
+            Action::Auction => {
+                let orderbook = &mut engine.orderbooks[0];
+                if orderbook.config.trading_state == TradingState::AUCTION {
+                    orderbook.auction_until = Some(0);
+                    engine.run_auctions(redis).await;
+                } else {
+                    orderbook.config.trading_state = TradingState::AUCTION;
+                    orderbook.auction_until = Some(i64::MAX);
+                }
+            }
+            _ => {}
         }
+    }
 
-        Ok(())
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn locked_funds_reconcile_with_open_orders(
+            actions in prop::collection::vec(action(), 1..60),
+        ) {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let redis = RedisManager::disconnected().unwrap();
+                let mut engine = engine(&USERS, dec!(10000), dec!(1000000));
+
+                for action in actions {
+                    apply(&mut engine, action, &redis).await;
+
+                    // every user has exactly what its open orders still need locked, and each order
+                    // has exactly that reserved
+                    let resting = resting_orders(&engine.orderbooks[0]);
+                    for user_id in USERS {
+                        for (asset, side) in [("USDC", OrderSide::BUY), ("SOL", OrderSide::SELL)] {
+                            let needed: Decimal = resting
+                                .iter()
+                                .filter(|order| order.user_id == user_id && order.side == side)
+                                .map(open_funds)
+                                .sum();
+                            prop_assert_eq!(engine.ledger.balance(user_id, asset).locked, needed);
+                        }
+                    }
+                    for order in resting.iter() {
+                        prop_assert_eq!(engine.ledger.reserved(&order.order_id), open_funds(order));
+                    }
+                }
+
+                engine.orderbooks[0].config.trading_state = TradingState::OPEN;
+                for _ in resting_orders(&engine.orderbooks[0]) {
+                    apply(&mut engine, Action::Cancel(0), &redis).await;
+                }
+                prop_assert!(resting_orders(&engine.orderbooks[0]).is_empty());
+
+                // once every order is closed nothing is locked, and no funds appeared or disappeared
+                for asset in ["USDC", "SOL"] {
+                    let mut total = engine.ledger.balance(&engine.fee_account, asset).available;
+                    for user_id in USERS {
+                        prop_assert_eq!(engine.ledger.balance(user_id, asset).locked, dec!(0));
+                        total += engine.ledger.balance(user_id, asset).available;
+                    }
+                    let deposited = if asset == "USDC" { dec!(3000000) } else { dec!(30000) };
+                    prop_assert_eq!(total, deposited);
+                }
+
+                Ok(())
+            })?;
+        }
     }
 }
@@ -0,0 +1,82 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const VOLUME_WINDOW_DAYS: i64 = 30;
+
+// a step of the fee schedule - users whose 30-day volume reaches `min_volume` get its discounts
+// off every market's maker and taker rates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub tier: u32,
+    pub min_volume: Decimal, // in quote asset
+    #[serde(default)]
+    pub maker_discount_percent: Decimal,
+    #[serde(default)]
+    pub taker_discount_percent: Decimal,
+}
+
+// the volume tiers and each user's traded volume over the last 30 days, kept in daily buckets
+#[derive(Debug, Clone, Default)]
+pub struct FeeSchedule {
+    pub tiers: Vec<FeeTier>,
+    volumes: HashMap<String, BTreeMap<i64, Decimal>>, // user id -> day -> quote volume
+}
+
+impl FeeSchedule {
+    pub fn new(mut tiers: Vec<FeeTier>) -> FeeSchedule {
+        tiers.sort_by_key(|fee_tier| fee_tier.min_volume);
+
+        FeeSchedule {
+            tiers,
+            volumes: HashMap::new(),
+        }
+    }
+
+    pub fn record_volume(&mut self, user_id: &str, timestamp: i64, volume: Decimal) {
+        let days = self.volumes.entry(user_id.to_string()).or_default();
+        *days.entry(timestamp / DAY_MILLIS).or_insert(dec!(0)) += volume;
+
+        // days that dropped out of the window are never read again
+        let first_day = timestamp / DAY_MILLIS - VOLUME_WINDOW_DAYS + 1;
+        days.retain(|day, _| *day >= first_day);
+    }
+
+    pub fn volume(&self, user_id: &str, now: i64) -> Decimal {
+        let first_day = now / DAY_MILLIS - VOLUME_WINDOW_DAYS + 1;
+
+        self.volumes
+            .get(user_id)
+            .map(|days| days.range(first_day..).map(|(_, volume)| *volume).sum())
+            .unwrap_or(dec!(0))
+    }
+
+    // the highest tier the user's volume reaches, if the schedule has one
+    pub fn tier(&self, user_id: &str, now: i64) -> Option<&FeeTier> {
+        let volume = self.volume(user_id, now);
+
+        self.tiers
+            .iter()
+            .rev()
+            .find(|fee_tier| volume >= fee_tier.min_volume)
+    }
+
+    // the market's maker and taker rates after the user's tier discounts
+    pub fn rates(
+        &self,
+        user_id: &str,
+        maker_fee_rate: Decimal,
+        taker_fee_rate: Decimal,
+        now: i64,
+    ) -> (Decimal, Decimal) {
+        match self.tier(user_id, now) {
+            Some(fee_tier) => (
+                maker_fee_rate * (dec!(100) - fee_tier.maker_discount_percent) / dec!(100),
+                taker_fee_rate * (dec!(100) - fee_tier.taker_discount_percent) / dec!(100),
+            ),
+            None => (maker_fee_rate, taker_fee_rate),
+        }
+    }
+}
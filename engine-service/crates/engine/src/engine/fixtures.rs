@@ -0,0 +1,101 @@
+// markets, orders and engines shared by the matching, ledger and engine tests
+use super::engine::Engine;
+use super::orderbook::OrderBook;
+use super::trigger_book::TriggerBook;
+use crate::types::engine::{
+    AssetConfig, AssetStatus, MarketConfig, MatchingPolicy, Order, OrderSide, OrderStatus,
+    OrderType, SelfTradePrevention, TimeInForce,
+};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+pub fn market_config(matching_policy: MatchingPolicy) -> MarketConfig {
+    let mut config: MarketConfig = serde_json::from_value(serde_json::json!({
+        "symbol": "SOL_USDC",
+        "base_asset": "SOL",
+        "quote_asset": "USDC",
+        "min_order_size": "1",
+        "max_order_size": "10000",
+        "price_precision": 2,
+        "quantity_precision": 0,
+    }))
+    .unwrap();
+    config.matching_policy = matching_policy;
+
+    config
+}
+
+pub fn orderbook(matching_policy: MatchingPolicy) -> OrderBook {
+    OrderBook::new(market_config(matching_policy), 0)
+}
+
+pub fn order(
+    order_id: &str,
+    user_id: &str,
+    side: OrderSide,
+    price: Decimal,
+    quantity: Decimal,
+) -> Order {
+    Order {
+        price,
+        quantity,
+        filled_quantity: dec!(0),
+        order_id: order_id.to_string(),
+        user_id: user_id.to_string(),
+        side,
+        order_type: OrderType::LIMIT,
+        order_status: OrderStatus::Pending,
+        timestamp: 0,
+        time_in_force: TimeInForce::GTC,
+        expires_at: None,
+        post_only: false,
+        post_only_reprice: false,
+        trigger_price: None,
+        trailing_offset: None,
+        trailing_percent: None,
+        oco_group_id: None,
+        display_quantity: None,
+        visible_quantity: None,
+        self_trade_prevention: SelfTradePrevention::CANCEL_NEWEST,
+    }
+}
+
+// an engine trading SOL_USDC with maker and taker fees, every user funded with `sol` and `usdc`
+pub fn engine(users: &[&str], sol: Decimal, usdc: Decimal) -> Engine {
+    let mut engine = Engine::new();
+    for (symbol, decimals) in [("SOL", 9), ("USDC", 6)] {
+        engine.assets.insert(
+            symbol.to_string(),
+            AssetConfig {
+                symbol: symbol.to_string(),
+                decimals,
+                status: AssetStatus::ACTIVE,
+            },
+        );
+    }
+
+    let mut config = market_config(MatchingPolicy::FIFO);
+    config.maker_fee_rate = dec!(0.001);
+    config.taker_fee_rate = dec!(0.002);
+    engine.orderbooks.push(OrderBook::new(config, 1));
+    engine
+        .trigger_books
+        .insert("SOL_USDC".to_string(), TriggerBook::new());
+
+    for user_id in users {
+        engine.ledger.deposit(user_id, "SOL", sol).unwrap();
+        engine.ledger.deposit(user_id, "USDC", usdc).unwrap();
+    }
+
+    engine
+}
+
+pub fn resting_orders(orderbook: &OrderBook) -> Vec<Order> {
+    orderbook
+        .bids
+        .values()
+        .chain(orderbook.asks.values())
+        .flatten()
+        .cloned()
+        .collect()
+}
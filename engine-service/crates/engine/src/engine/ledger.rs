@@ -0,0 +1,450 @@
+use crate::types::engine::{Order, OrderSide, OrderStatus};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: Decimal,
+    pub locked: Decimal,
+}
+
+// one change the ledger applied, with the balance it left - what gets persisted and passed downstream
+#[derive(Debug, Clone)]
+pub struct BalanceChange {
+    pub user_id: String,
+    pub asset: String,
+    pub available_change: Decimal,
+    pub locked_change: Decimal,
+    pub balance: Balance,
+}
+
+// one trade as the ledger settles it - the buyer pays `cost` in quote and the seller `quantity` in base,
+// each out of its order's reservation, and each is credited what the other paid less its fee
+#[derive(Debug, Clone)]
+pub struct Settlement<'a> {
+    pub buyer_id: &'a str,
+    pub buy_order_id: &'a str,
+    pub seller_id: &'a str,
+    pub sell_order_id: &'a str,
+    pub base_asset: &'a str,
+    pub quote_asset: &'a str,
+    pub quantity: Decimal,
+    pub cost: Decimal,
+    pub buyer_fee: Decimal,  // in base
+    pub seller_fee: Decimal, // in quote
+    pub fee_account: &'a str,
+}
+
+// what an order still needs locked - its remaining quantity at its price for buys, the remaining
+// quantity for sells, and nothing once it is filled or cancelled
+pub fn open_funds(order: &Order) -> Decimal {
+    if matches!(
+        order.order_status,
+        OrderStatus::Filled | OrderStatus::Cancelled
+    ) {
+        return dec!(0);
+    }
+    let remaining_quantity = order.quantity - order.filled_quantity;
+
+    match order.side {
+        OrderSide::BUY => remaining_quantity * order.price,
+        OrderSide::SELL => remaining_quantity,
+    }
+}
+
+// funds locked for one order that it has not paid out yet - quote for buys, base for sells
+#[derive(Debug, Clone)]
+struct Reservation {
+    user_id: String,
+    asset: String,
+    amount: Decimal,
+}
+
+// available and locked funds of every user per asset. the engine owns it, so pre-trade checks never
+// leave the process - deposits, withdrawals, order locks and fills all go through here
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    balances: HashMap<String, HashMap<String, Balance>>, // user id -> asset symbol -> balance
+    reservations: HashMap<String, Reservation>,          // order id -> what is still locked for it
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger {
+            balances: HashMap::new(),
+            reservations: HashMap::new(),
+        }
+    }
+
+    // restores a balance persisted before a restart
+    pub fn load(&mut self, user_id: &str, asset: &str, balance: Balance) {
+        self.balances
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(asset.to_string(), balance);
+    }
+
+    pub fn open_account(&mut self, user_id: &str) {
+        self.balances.entry(user_id.to_string()).or_default();
+    }
+
+    pub fn balance(&self, user_id: &str, asset: &str) -> Balance {
+        self.balances
+            .get(user_id)
+            .and_then(|balances| balances.get(asset))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn deposit(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        if amount <= dec!(0) {
+            return Err("Amount must be positive");
+        }
+
+        self.apply(user_id, asset, amount, dec!(0))
+    }
+
+    pub fn withdraw(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        if amount <= dec!(0) {
+            return Err("Amount must be positive");
+        }
+
+        self.apply(user_id, asset, -amount, dec!(0))
+    }
+
+    // moves available funds to locked for an order
+    pub fn lock(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        self.apply(user_id, asset, -amount, amount)
+    }
+
+    // moves locked funds an order no longer needs back to available
+    pub fn unlock(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        self.apply(user_id, asset, amount, -amount)
+    }
+
+    // locks funds for an order and records them against it, on top of anything it already reserved
+    pub fn reserve(
+        &mut self,
+        order_id: &str,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        let change = self.lock(user_id, asset, amount)?;
+        self.load_reservation(order_id, user_id, asset, amount);
+
+        Ok(change)
+    }
+
+    // records funds already locked for an order, e.g. one restored after a restart
+    pub fn load_reservation(
+        &mut self,
+        order_id: &str,
+        user_id: &str,
+        asset: &str,
+        amount: Decimal,
+    ) {
+        self.reservations
+            .entry(order_id.to_string())
+            .or_insert_with(|| Reservation {
+                user_id: user_id.to_string(),
+                asset: asset.to_string(),
+                amount: dec!(0),
+            })
+            .amount += amount;
+    }
+
+    pub fn reserved(&self, order_id: &str) -> Decimal {
+        self.reservations
+            .get(order_id)
+            .map(|reservation| reservation.amount)
+            .unwrap_or(dec!(0))
+    }
+
+    // takes what an order paid for a fill off its reservation - the funds themselves leave through settle
+    fn use_reserved(&mut self, order_id: &str, amount: Decimal) {
+        if let Some(reservation) = self.reservations.get_mut(order_id) {
+            reservation.amount -= amount;
+        }
+    }
+
+    // unlocks what an order has reserved beyond `needed`, the funds its open quantity still needs. the
+    // reservation is dropped once nothing is needed, so a closed order always ends up with nothing locked
+    pub fn release_surplus(
+        &mut self,
+        order_id: &str,
+        needed: Decimal,
+    ) -> Result<Option<BalanceChange>, &'static str> {
+        let reservation = match self.reservations.get(order_id) {
+            Some(reservation) => reservation.clone(),
+            None => return Ok(None),
+        };
+
+        let surplus = reservation.amount - needed;
+        let change = if surplus > dec!(0) {
+            Some(self.unlock(&reservation.user_id, &reservation.asset, surplus)?)
+        } else {
+            None
+        };
+
+        if needed > dec!(0) {
+            if let Some(reservation) = self.reservations.get_mut(order_id) {
+                reservation.amount = std::cmp::min(reservation.amount, needed);
+            }
+        } else {
+            self.reservations.remove(order_id);
+        }
+
+        Ok(change)
+    }
+
+    // checks trades as if they settled one after another, without applying any - each side has to pay out
+    // of what its order reserved, and no balance the trades touch may go below zero
+    pub fn check_settlements(&self, settlements: &[Settlement]) -> Result<(), &'static str> {
+        let mut balances: HashMap<(&str, &str), Balance> = HashMap::new();
+        let mut reserved: HashMap<&str, Decimal> = HashMap::new();
+
+        for settlement in settlements {
+            for (order_id, amount) in [
+                (settlement.buy_order_id, settlement.cost),
+                (settlement.sell_order_id, settlement.quantity),
+            ] {
+                let remaining = reserved
+                    .entry(order_id)
+                    .or_insert_with(|| self.reserved(order_id));
+                *remaining -= amount;
+                if *remaining < dec!(0) {
+                    return Err("Insufficient reserved funds");
+                }
+            }
+
+            // every balance the trade touches, as the trade leaves it
+            for (user_id, asset, available_change, locked_change) in Self::legs(settlement) {
+                let balance = balances
+                    .entry((user_id, asset))
+                    .or_insert_with(|| self.balance(user_id, asset));
+                balance.available += available_change;
+                balance.locked += locked_change;
+            }
+            if balances.values().any(|balance| balance.available < dec!(0)) {
+                return Err("Insufficient funds");
+            }
+            if balances.values().any(|balance| balance.locked < dec!(0)) {
+                return Err("Insufficient locked funds");
+            }
+        }
+
+        Ok(())
+    }
+
+    // applies every balance change of a trade or none of them - a trade one side cannot pay for is
+    // rejected before anything moves, so it never credits funds that were not debited
+    pub fn settle(&mut self, settlement: &Settlement) -> Result<Vec<BalanceChange>, &'static str> {
+        self.check_settlements(std::slice::from_ref(settlement))?;
+
+        let mut changes = Vec::new();
+        for (user_id, asset, available_change, locked_change) in Self::legs(settlement) {
+            changes.push(self.apply(user_id, asset, available_change, locked_change)?);
+        }
+        self.use_reserved(settlement.buy_order_id, settlement.cost);
+        self.use_reserved(settlement.sell_order_id, settlement.quantity);
+
+        Ok(changes)
+    }
+
+    // the balance changes of a trade as user, asset, available change and locked change
+    fn legs<'a>(settlement: &Settlement<'a>) -> Vec<(&'a str, &'a str, Decimal, Decimal)> {
+        let mut legs = vec![
+            (
+                settlement.buyer_id,
+                settlement.quote_asset,
+                dec!(0),
+                -settlement.cost,
+            ),
+            (
+                settlement.buyer_id,
+                settlement.base_asset,
+                settlement.quantity - settlement.buyer_fee,
+                dec!(0),
+            ),
+            (
+                settlement.seller_id,
+                settlement.base_asset,
+                dec!(0),
+                -settlement.quantity,
+            ),
+            (
+                settlement.seller_id,
+                settlement.quote_asset,
+                settlement.cost - settlement.seller_fee,
+                dec!(0),
+            ),
+        ];
+        if settlement.buyer_fee > dec!(0) {
+            legs.push((
+                settlement.fee_account,
+                settlement.base_asset,
+                settlement.buyer_fee,
+                dec!(0),
+            ));
+        }
+        if settlement.seller_fee > dec!(0) {
+            legs.push((
+                settlement.fee_account,
+                settlement.quote_asset,
+                settlement.seller_fee,
+                dec!(0),
+            ));
+        }
+
+        legs
+    }
+
+    // a change that would take either side of the balance below zero is rejected and leaves it untouched
+    fn apply(
+        &mut self,
+        user_id: &str,
+        asset: &str,
+        available_change: Decimal,
+        locked_change: Decimal,
+    ) -> Result<BalanceChange, &'static str> {
+        let mut balance = self.balance(user_id, asset);
+        balance.available += available_change;
+        balance.locked += locked_change;
+
+        if balance.available < dec!(0) {
+            return Err("Insufficient funds");
+        }
+        if balance.locked < dec!(0) {
+            return Err("Insufficient locked funds");
+        }
+
+        self.load(user_id, asset, balance);
+
+        Ok(BalanceChange {
+            user_id: user_id.to_string(),
+            asset: asset.to_string(),
+            available_change,
+            locked_change,
+            balance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ledger, Settlement};
+    use rust_decimal_macros::dec;
+
+    fn settlement(quantity: rust_decimal::Decimal) -> Settlement<'static> {
+        Settlement {
+            buyer_id: "alice",
+            buy_order_id: "b",
+            seller_id: "bob",
+            sell_order_id: "s",
+            base_asset: "SOL",
+            quote_asset: "USDC",
+            quantity,
+            cost: quantity * dec!(100),
+            buyer_fee: dec!(0.01),
+            seller_fee: dec!(1),
+            fee_account: "fees",
+        }
+    }
+
+    #[test]
+    fn settlement_pays_out_of_both_reservations() {
+        let mut ledger = Ledger::new();
+        ledger.deposit("alice", "USDC", dec!(1000)).unwrap();
+        ledger.deposit("bob", "SOL", dec!(10)).unwrap();
+        ledger.reserve("b", "alice", "USDC", dec!(500)).unwrap();
+        ledger.reserve("s", "bob", "SOL", dec!(5)).unwrap();
+
+        ledger.settle(&settlement(dec!(2))).unwrap();
+
+        assert_eq!(ledger.balance("alice", "USDC").locked, dec!(300));
+        assert_eq!(ledger.balance("alice", "SOL").available, dec!(1.99));
+        assert_eq!(ledger.balance("bob", "SOL").locked, dec!(3));
+        assert_eq!(ledger.balance("bob", "USDC").available, dec!(199));
+        assert_eq!(ledger.balance("fees", "SOL").available, dec!(0.01));
+        assert_eq!(ledger.balance("fees", "USDC").available, dec!(1));
+        assert_eq!(ledger.reserved("b"), dec!(300));
+        assert_eq!(ledger.reserved("s"), dec!(3));
+    }
+
+    #[test]
+    fn settlement_one_side_cannot_pay_moves_nothing() {
+        let mut ledger = Ledger::new();
+        ledger.deposit("alice", "USDC", dec!(1000)).unwrap();
+        ledger.deposit("bob", "SOL", dec!(10)).unwrap();
+        ledger.reserve("b", "alice", "USDC", dec!(500)).unwrap();
+        // the seller only reserved 1 of the 2 it is about to deliver for this order - it has more locked,
+        // but that belongs to another of its orders
+        ledger.reserve("s", "bob", "SOL", dec!(1)).unwrap();
+        ledger.reserve("other", "bob", "SOL", dec!(5)).unwrap();
+        let before = ledger.clone();
+
+        assert_eq!(
+            ledger.settle(&settlement(dec!(2))).unwrap_err(),
+            "Insufficient reserved funds"
+        );
+
+        for (user_id, asset) in [
+            ("alice", "USDC"),
+            ("alice", "SOL"),
+            ("bob", "SOL"),
+            ("bob", "USDC"),
+            ("fees", "SOL"),
+            ("fees", "USDC"),
+        ] {
+            assert_eq!(
+                ledger.balance(user_id, asset),
+                before.balance(user_id, asset)
+            );
+        }
+        assert_eq!(ledger.reserved("b"), dec!(500));
+        assert_eq!(ledger.reserved("s"), dec!(1));
+        assert_eq!(ledger.reserved("other"), dec!(5));
+    }
+
+    #[test]
+    fn settlements_are_checked_against_what_is_left_of_the_reservations() {
+        let mut ledger = Ledger::new();
+        ledger.deposit("alice", "USDC", dec!(1000)).unwrap();
+        ledger.deposit("bob", "SOL", dec!(10)).unwrap();
+        ledger.reserve("b", "alice", "USDC", dec!(500)).unwrap();
+        ledger.reserve("s", "bob", "SOL", dec!(5)).unwrap();
+
+        // each trade fits the reservations on its own, but the two together need 6 of the 5 reserved
+        let settlements = [settlement(dec!(3)), settlement(dec!(3))];
+        assert!(ledger.check_settlements(&settlements[..1]).is_ok());
+        assert_eq!(
+            ledger.check_settlements(&settlements).unwrap_err(),
+            "Insufficient reserved funds"
+        );
+        assert_eq!(ledger.reserved("b"), dec!(500));
+        assert_eq!(ledger.reserved("s"), dec!(5));
+    }
+}
@@ -0,0 +1,792 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::types::engine::{
+    Fill, MatchingPolicy, Order, OrderStatus, ProcessOrderResult, SelfTradePrevention,
+};
+
+// shares an incoming order out among the resting orders of one price level. the orderbook walks the
+// levels in price priority and hands each crossing level to the market's algorithm
+pub trait MatchingAlgorithm {
+    // fills the incoming order against the level, recording fills and self-trade cancels in `order_result`,
+    // and takes completely filled orders off the level. `lot_size` is the smallest quantity the market trades.
+    // returns false once the incoming order has nothing left to match
+    fn match_level(
+        &self,
+        order: &Order,
+        resting_orders: &mut Vec<Order>,
+        trade_id: &mut i64,
+        lot_size: Decimal,
+        order_result: &mut ProcessOrderResult,
+    ) -> bool;
+}
+
+pub struct Fifo;
+pub struct ProRata;
+pub struct Hybrid;
+
+pub fn matching_algorithm(matching_policy: &MatchingPolicy) -> &'static dyn MatchingAlgorithm {
+    match matching_policy {
+        MatchingPolicy::FIFO => &Fifo,
+        MatchingPolicy::PRO_RATA => &ProRata,
+        MatchingPolicy::HYBRID => &Hybrid,
+    }
+}
+
+// fills in time priority - an iceberg whose visible slice runs out is refilled from its hidden reserve
+// and moves to the back of the level
+impl MatchingAlgorithm for Fifo {
+    fn match_level(
+        &self,
+        order: &Order,
+        resting_orders: &mut Vec<Order>,
+        trade_id: &mut i64,
+        _lot_size: Decimal,
+        order_result: &mut ProcessOrderResult,
+    ) -> bool {
+        let mut index = 0;
+        let mut matching = true;
+
+        while index < resting_orders.len() {
+            if remaining_quantity(order, order_result) <= dec!(0) {
+                matching = false;
+                break;
+            }
+
+            if resting_orders[index].user_id == order.user_id {
+                if !prevent_self_trade(order, resting_orders, index, order_result) {
+                    matching = false;
+                    break;
+                }
+                continue;
+            }
+
+            let filled_quantity = std::cmp::min(
+                remaining_quantity(order, order_result),
+                available_quantity(&resting_orders[index]),
+            );
+            if filled_quantity <= dec!(0) {
+                matching = false;
+                break;
+            }
+
+            if !fill(
+                resting_orders,
+                index,
+                filled_quantity,
+                trade_id,
+                order_result,
+            ) {
+                index += 1;
+            }
+        }
+
+        remove_filled(resting_orders);
+
+        matching
+    }
+}
+
+// fills every order at the level in proportion to what it shows
+impl MatchingAlgorithm for ProRata {
+    fn match_level(
+        &self,
+        order: &Order,
+        resting_orders: &mut Vec<Order>,
+        trade_id: &mut i64,
+        lot_size: Decimal,
+        order_result: &mut ProcessOrderResult,
+    ) -> bool {
+        if !prevent_self_trades(order, resting_orders, order_result) {
+            remove_filled(resting_orders);
+            return false;
+        }
+
+        allocate_pro_rata(order, resting_orders, trade_id, lot_size, order_result);
+        remove_filled(resting_orders);
+
+        remaining_quantity(order, order_result) > dec!(0)
+    }
+}
+
+// the oldest order at the level fills first, whatever is left is shared pro-rata
+impl MatchingAlgorithm for Hybrid {
+    fn match_level(
+        &self,
+        order: &Order,
+        resting_orders: &mut Vec<Order>,
+        trade_id: &mut i64,
+        lot_size: Decimal,
+        order_result: &mut ProcessOrderResult,
+    ) -> bool {
+        if !prevent_self_trades(order, resting_orders, order_result) {
+            remove_filled(resting_orders);
+            return false;
+        }
+
+        if let Some(top_order) = resting_orders.first() {
+            let filled_quantity = std::cmp::min(
+                remaining_quantity(order, order_result),
+                available_quantity(top_order),
+            );
+            if filled_quantity > dec!(0) {
+                fill(resting_orders, 0, filled_quantity, trade_id, order_result);
+            }
+        }
+
+        allocate_pro_rata(order, resting_orders, trade_id, lot_size, order_result);
+        remove_filled(resting_orders);
+
+        remaining_quantity(order, order_result) > dec!(0)
+    }
+}
+
+fn remaining_quantity(order: &Order, order_result: &ProcessOrderResult) -> Decimal {
+    order.quantity - order_result.executed_quantity - order_result.cancelled_quantity
+}
+
+// what a resting order can fill right now - an iceberg only its visible slice
+fn available_quantity(resting_order: &Order) -> Decimal {
+    resting_order
+        .visible_quantity
+        .unwrap_or(resting_order.quantity - resting_order.filled_quantity)
+}
+
+// shares the incoming order out in rounds, each order getting its share of the level rounded down to the
+// lot size. the lots lost to rounding go one at a time to the oldest orders, and icebergs refilled during a
+// round take part in the next one
+fn allocate_pro_rata(
+    order: &Order,
+    resting_orders: &mut Vec<Order>,
+    trade_id: &mut i64,
+    lot_size: Decimal,
+    order_result: &mut ProcessOrderResult,
+) {
+    loop {
+        let remaining_quantity = remaining_quantity(order, order_result);
+        let available: Vec<Decimal> = resting_orders.iter().map(available_quantity).collect();
+        let total_available: Decimal = available.iter().sum();
+        if remaining_quantity <= dec!(0) || total_available <= dec!(0) {
+            return;
+        }
+
+        let mut allocations: Vec<Decimal> = if remaining_quantity >= total_available {
+            available.clone()
+        } else {
+            available
+                .iter()
+                .map(|available| {
+                    (remaining_quantity * available / total_available / lot_size).floor() * lot_size
+                })
+                .collect()
+        };
+
+        let mut leftover = std::cmp::min(remaining_quantity, total_available)
+            - allocations.iter().sum::<Decimal>();
+        while leftover > dec!(0) {
+            let mut allocated = false;
+            for (allocation, available) in allocations.iter_mut().zip(available.iter()) {
+                let lot =
+                    std::cmp::min(lot_size, std::cmp::min(leftover, *available - *allocation));
+                if lot > dec!(0) {
+                    *allocation += lot;
+                    leftover -= lot;
+                    allocated = true;
+                }
+                if leftover <= dec!(0) {
+                    break;
+                }
+            }
+            if !allocated {
+                break;
+            }
+        }
+
+        // refilled icebergs move to the back of the level, so the fills follow the orders by id
+        let allocations: Vec<(String, Decimal)> = resting_orders
+            .iter()
+            .zip(allocations)
+            .filter(|(_, allocation)| *allocation > dec!(0))
+            .map(|(resting_order, allocation)| (resting_order.order_id.clone(), allocation))
+            .collect();
+        for (order_id, allocation) in allocations {
+            if let Some(index) = resting_orders
+                .iter()
+                .position(|resting_order| resting_order.order_id == order_id)
+            {
+                fill(resting_orders, index, allocation, trade_id, order_result);
+            }
+        }
+    }
+}
+
+// fills `filled_quantity` of the resting order at `index`. returns true if it was an iceberg whose
+// visible slice ran out, which is refilled and moved to the back of the level
+fn fill(
+    resting_orders: &mut Vec<Order>,
+    index: usize,
+    filled_quantity: Decimal,
+    trade_id: &mut i64,
+    order_result: &mut ProcessOrderResult,
+) -> bool {
+    let resting_order = &mut resting_orders[index];
+
+    *trade_id += 1;
+    order_result.executed_quantity += filled_quantity;
+    resting_order.filled_quantity += filled_quantity;
+    resting_order.order_status = if resting_order.filled_quantity >= resting_order.quantity {
+        OrderStatus::Filled
+    } else {
+        OrderStatus::PartiallyFilled
+    };
+
+    order_result.fills.push(Fill {
+        price: resting_order.price,
+        quantity: filled_quantity,
+        trade_id: *trade_id,
+        other_user_id: resting_order.user_id.clone(),
+        order_id: resting_order.order_id.clone(),
+        taker_fee: dec!(0),
+        maker_fee: dec!(0),
+    });
+
+    if let (Some(display_quantity), Some(visible_quantity)) = (
+        resting_order.display_quantity,
+        resting_order.visible_quantity,
+    ) {
+        let visible_quantity = visible_quantity - filled_quantity;
+        let hidden_quantity = resting_order.quantity - resting_order.filled_quantity;
+
+        if visible_quantity <= dec!(0) && hidden_quantity > dec!(0) {
+            resting_order.visible_quantity = Some(std::cmp::min(display_quantity, hidden_quantity));
+            record_filled_order(order_result, resting_order);
+
+            let refilled_order = resting_orders.remove(index);
+            resting_orders.push(refilled_order);
+            return true;
+        }
+
+        resting_order.visible_quantity = Some(visible_quantity);
+    }
+
+    record_filled_order(order_result, resting_order);
+    false
+}
+
+// keeps one snapshot per resting order so the maker side can be persisted once matching is done
+fn record_filled_order(order_result: &mut ProcessOrderResult, resting_order: &Order) {
+    order_result
+        .filled_orders
+        .retain(|filled_order| filled_order.order_id != resting_order.order_id);
+    order_result.filled_orders.push(resting_order.clone());
+}
+
+// Remove orders that have been completely filled
+fn remove_filled(resting_orders: &mut Vec<Order>) {
+    resting_orders.retain(|resting_order| resting_order.filled_quantity < resting_order.quantity);
+}
+
+// applies self-trade prevention against every order of the same user at the level before it is shared
+// out. returns false if the incoming order was cancelled
+fn prevent_self_trades(
+    order: &Order,
+    resting_orders: &mut Vec<Order>,
+    order_result: &mut ProcessOrderResult,
+) -> bool {
+    let mut index = 0;
+
+    while index < resting_orders.len() {
+        if remaining_quantity(order, order_result) <= dec!(0) {
+            return false;
+        }
+
+        if resting_orders[index].user_id != order.user_id {
+            index += 1;
+            continue;
+        }
+
+        let level_size = resting_orders.len();
+        if !prevent_self_trade(order, resting_orders, index, order_result) {
+            return false;
+        }
+        if resting_orders.len() == level_size {
+            index += 1;
+        }
+    }
+
+    true
+}
+
+// applies the incoming order's self-trade prevention against the resting order at `index`, taking
+// cancelled resting orders off the level and marking them cancelled - a decremented order keeps resting
+// with its status. returns false if the incoming order was cancelled
+fn prevent_self_trade(
+    order: &Order,
+    resting_orders: &mut Vec<Order>,
+    index: usize,
+    order_result: &mut ProcessOrderResult,
+) -> bool {
+    let remaining_quantity = remaining_quantity(order, order_result);
+    let resting_order = &mut resting_orders[index];
+    let resting_quantity = resting_order.quantity - resting_order.filled_quantity;
+
+    match order.self_trade_prevention {
+        SelfTradePrevention::CANCEL_NEWEST => {
+            order_result.cancelled_quantity += remaining_quantity;
+            false
+        }
+        SelfTradePrevention::CANCEL_OLDEST => {
+            let mut resting_order = resting_orders.remove(index);
+            resting_order.order_status = OrderStatus::Cancelled;
+            order_result
+                .cancelled_orders
+                .push((resting_order, resting_quantity));
+            true
+        }
+        SelfTradePrevention::CANCEL_BOTH => {
+            let mut resting_order = resting_orders.remove(index);
+            resting_order.order_status = OrderStatus::Cancelled;
+            order_result
+                .cancelled_orders
+                .push((resting_order, resting_quantity));
+            order_result.cancelled_quantity += remaining_quantity;
+            false
+        }
+        SelfTradePrevention::DECREMENT_AND_CANCEL => {
+            let decrement = std::cmp::min(remaining_quantity, resting_quantity);
+            order_result.cancelled_quantity += decrement;
+
+            if decrement < resting_quantity {
+                resting_order.quantity -= decrement;
+                resting_order.visible_quantity =
+                    resting_order.visible_quantity.map(|visible_quantity| {
+                        std::cmp::min(visible_quantity, resting_quantity - decrement)
+                    });
+                order_result
+                    .cancelled_orders
+                    .push((resting_order.clone(), decrement));
+            } else {
+                let mut resting_order = resting_orders.remove(index);
+                resting_order.order_status = OrderStatus::Cancelled;
+                order_result
+                    .cancelled_orders
+                    .push((resting_order, decrement));
+            }
+
+            decrement < remaining_quantity
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{order, orderbook};
+    use super::super::orderbook::OrderBook;
+    use crate::types::engine::{
+        MatchingPolicy, OrderSide, OrderStatus, OrderType, SelfTradePrevention, TimeInForce,
+    };
+    use proptest::prelude::*;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    fn resting_quantity(orderbook: &OrderBook, order_id: &str) -> Option<Decimal> {
+        orderbook
+            .bids
+            .values()
+            .chain(orderbook.asks.values())
+            .flatten()
+            .find(|order| order.order_id == order_id)
+            .map(|order| order.quantity - order.filled_quantity)
+    }
+
+    fn filled_quantity(fills: &[super::Fill], order_id: &str) -> Decimal {
+        fills
+            .iter()
+            .filter(|fill| fill.order_id == order_id)
+            .map(|fill| fill.quantity)
+            .sum()
+    }
+
+    // the behaviour every matching algorithm must share - they may only differ in how one price level
+    // is shared out
+    fn conformance(matching_policy: MatchingPolicy) {
+        // better prices fill first, at the resting order's price
+        let mut book = orderbook(matching_policy.clone());
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(101), dec!(2)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(101), dec!(3)))
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(3));
+        assert_eq!(result.fills[0].price, dec!(100));
+        assert_eq!(filled_quantity(&result.fills, "b"), dec!(2));
+        assert_eq!(filled_quantity(&result.fills, "a"), dec!(1));
+        assert_eq!(resting_quantity(&book, "a"), Some(dec!(1)));
+        assert_eq!(resting_quantity(&book, "b"), None);
+
+        // nothing trades through the limit price, the remainder rests
+        let mut book = orderbook(matching_policy.clone());
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(1)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(101), dec!(1)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(2)))
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(1));
+        assert_eq!(resting_quantity(&book, "b"), Some(dec!(1)));
+        assert_eq!(resting_quantity(&book, "t"), Some(dec!(1)));
+
+        // fills never exceed the incoming order, and take exactly what they fill off the resting orders
+        let mut book = orderbook(matching_policy.clone());
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(3)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(100), dec!(5)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(4)))
+            .unwrap();
+        let fills_total: Decimal = result.fills.iter().map(|fill| fill.quantity).sum();
+        assert_eq!(result.executed_quantity, dec!(4));
+        assert_eq!(fills_total, dec!(4));
+        assert_eq!(
+            resting_quantity(&book, "a").unwrap_or(dec!(0))
+                + resting_quantity(&book, "b").unwrap_or(dec!(0)),
+            dec!(4)
+        );
+
+        // a partly filled resting order only fills what it has left
+        let mut book = orderbook(matching_policy.clone());
+        let mut partly_filled = order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(5));
+        partly_filled.filled_quantity = dec!(3);
+        book.restore_order(partly_filled);
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(5)))
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(2));
+        assert_eq!(resting_quantity(&book, "a"), None);
+        assert_eq!(resting_quantity(&book, "t"), Some(dec!(3)));
+
+        // self-trade prevention cancels the incoming order before it trades with its own user
+        let mut book = orderbook(matching_policy.clone());
+        book.process_order(order("a", "taker", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(2)))
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(0));
+        assert_eq!(result.cancelled_quantity, dec!(2));
+        assert_eq!(resting_quantity(&book, "a"), Some(dec!(2)));
+
+        // icebergs fill beyond their visible slice once it is refilled
+        let mut book = orderbook(matching_policy);
+        let mut iceberg = order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(10));
+        iceberg.display_quantity = Some(dec!(2));
+        book.process_order(iceberg).unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(5)))
+            .unwrap();
+        assert_eq!(result.executed_quantity, dec!(5));
+        assert_eq!(resting_quantity(&book, "a"), Some(dec!(5)));
+    }
+
+    #[test]
+    fn fifo_conforms() {
+        conformance(MatchingPolicy::FIFO);
+    }
+
+    #[test]
+    fn pro_rata_conforms() {
+        conformance(MatchingPolicy::PRO_RATA);
+    }
+
+    #[test]
+    fn hybrid_conforms() {
+        conformance(MatchingPolicy::HYBRID);
+    }
+
+    #[test]
+    fn fifo_fills_in_time_priority() {
+        let mut book = orderbook(MatchingPolicy::FIFO);
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(2)))
+            .unwrap();
+
+        assert_eq!(filled_quantity(&result.fills, "a"), dec!(2));
+        assert_eq!(filled_quantity(&result.fills, "b"), dec!(0));
+    }
+
+    #[test]
+    fn pro_rata_shares_the_level_by_size() {
+        let mut book = orderbook(MatchingPolicy::PRO_RATA);
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(6)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(100), dec!(3)))
+            .unwrap();
+        book.process_order(order("c", "maker-c", OrderSide::SELL, dec!(100), dec!(1)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(5)))
+            .unwrap();
+
+        // 3, 1.5 and 0.5 round down to whole lots, the lot left over goes to the oldest order
+        assert_eq!(filled_quantity(&result.fills, "a"), dec!(4));
+        assert_eq!(filled_quantity(&result.fills, "b"), dec!(1));
+        assert_eq!(filled_quantity(&result.fills, "c"), dec!(0));
+    }
+
+    #[test]
+    fn decrement_and_cancel_only_cancels_the_resting_order_it_takes_off_the_book() {
+        let mut book = orderbook(MatchingPolicy::FIFO);
+        book.process_order(order("a", "alice", OrderSide::SELL, dec!(100), dec!(10)))
+            .unwrap();
+
+        let mut incoming = order("t", "alice", OrderSide::BUY, dec!(100), dec!(5));
+        incoming.self_trade_prevention = SelfTradePrevention::DECREMENT_AND_CANCEL;
+        let result = book.process_order(incoming).unwrap();
+
+        // 5 of the 10 are left resting, so the order is decremented but still open
+        let (decremented, cancelled_quantity) = &result.cancelled_orders[0];
+        assert_eq!(*cancelled_quantity, dec!(5));
+        assert_eq!(decremented.quantity, dec!(5));
+        assert!(matches!(decremented.order_status, OrderStatus::Pending));
+        assert_eq!(resting_quantity(&book, "a"), Some(dec!(5)));
+
+        let mut incoming = order("u", "alice", OrderSide::BUY, dec!(100), dec!(5));
+        incoming.self_trade_prevention = SelfTradePrevention::DECREMENT_AND_CANCEL;
+        let result = book.process_order(incoming).unwrap();
+
+        let (removed, cancelled_quantity) = &result.cancelled_orders[0];
+        assert_eq!(*cancelled_quantity, dec!(5));
+        assert!(matches!(removed.order_status, OrderStatus::Cancelled));
+        assert_eq!(resting_quantity(&book, "a"), None);
+    }
+
+    #[test]
+    fn fill_or_kill_only_counts_liquidity_self_trade_prevention_lets_it_take() {
+        for self_trade_prevention in [
+            SelfTradePrevention::CANCEL_OLDEST,
+            SelfTradePrevention::CANCEL_BOTH,
+            SelfTradePrevention::DECREMENT_AND_CANCEL,
+        ] {
+            let mut book = orderbook(MatchingPolicy::FIFO);
+            book.process_order(order("own", "alice", OrderSide::SELL, dec!(100), dec!(2)))
+                .unwrap();
+            book.process_order(order("b", "bob", OrderSide::SELL, dec!(100), dec!(2)))
+                .unwrap();
+
+            // 4 rest at the price, but only bob's 2 can trade with alice
+            let mut incoming = order("t", "alice", OrderSide::BUY, dec!(100), dec!(4));
+            incoming.time_in_force = TimeInForce::FOK;
+            incoming.self_trade_prevention = self_trade_prevention;
+            let result = book.process_order(incoming).unwrap();
+
+            assert_eq!(result.executed_quantity, dec!(0));
+            assert_eq!(result.cancelled_quantity, dec!(4));
+            assert!(result.cancelled_orders.is_empty());
+            assert_eq!(resting_quantity(&book, "own"), Some(dec!(2)));
+            assert_eq!(resting_quantity(&book, "b"), Some(dec!(2)));
+        }
+
+        // with enough of other users' liquidity behind its own order it fills in full
+        let mut book = orderbook(MatchingPolicy::FIFO);
+        book.process_order(order("own", "alice", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        book.process_order(order("b", "bob", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        book.process_order(order("c", "bob", OrderSide::SELL, dec!(101), dec!(2)))
+            .unwrap();
+        let mut incoming = order("t", "alice", OrderSide::BUY, dec!(101), dec!(4));
+        incoming.time_in_force = TimeInForce::FOK;
+        incoming.self_trade_prevention = SelfTradePrevention::CANCEL_OLDEST;
+        let result = book.process_order(incoming).unwrap();
+
+        assert_eq!(result.executed_quantity, dec!(4));
+        assert_eq!(resting_quantity(&book, "own"), None);
+    }
+
+    #[test]
+    fn market_buy_quantity_only_counts_asks_the_order_can_take() {
+        let mut book = orderbook(MatchingPolicy::FIFO);
+        book.config.price_band_percent = Some(dec!(10));
+        book.config.reference_price = Some(dec!(100));
+        book.process_order(order("own", "alice", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        book.process_order(order("b", "bob", OrderSide::SELL, dec!(101), dec!(2)))
+            .unwrap();
+        book.process_order(order("c", "bob", OrderSide::SELL, dec!(120), dec!(5)))
+            .unwrap();
+
+        let mut market_buy = order("t", "alice", OrderSide::BUY, dec!(0), dec!(0));
+        market_buy.order_type = OrderType::MARKET;
+
+        // alice's own ask is cancelled rather than bought and the ask at 120 is outside the band
+        market_buy.self_trade_prevention = SelfTradePrevention::CANCEL_OLDEST;
+        assert_eq!(book.market_buy_quantity(&market_buy, dec!(1000)), dec!(2));
+        assert_eq!(book.market_buy_quantity(&market_buy, dec!(150)), dec!(1));
+
+        // an order that is cancelled when it meets its own ask buys nothing
+        market_buy.self_trade_prevention = SelfTradePrevention::CANCEL_NEWEST;
+        assert_eq!(book.market_buy_quantity(&market_buy, dec!(1000)), dec!(0));
+
+        // another user buys every ask inside the band, as far as the amount goes
+        market_buy.user_id = "carol".to_string();
+        assert_eq!(book.market_buy_quantity(&market_buy, dec!(1000)), dec!(4));
+        assert_eq!(book.market_buy_quantity(&market_buy, dec!(300)), dec!(2));
+    }
+
+    #[test]
+    fn hybrid_fills_the_top_order_first() {
+        let mut book = orderbook(MatchingPolicy::HYBRID);
+        book.process_order(order("a", "maker-a", OrderSide::SELL, dec!(100), dec!(2)))
+            .unwrap();
+        book.process_order(order("b", "maker-b", OrderSide::SELL, dec!(100), dec!(4)))
+            .unwrap();
+        book.process_order(order("c", "maker-c", OrderSide::SELL, dec!(100), dec!(4)))
+            .unwrap();
+        let result = book
+            .process_order(order("t", "taker", OrderSide::BUY, dec!(100), dec!(6)))
+            .unwrap();
+
+        assert_eq!(filled_quantity(&result.fills, "a"), dec!(2));
+        assert_eq!(filled_quantity(&result.fills, "b"), dec!(2));
+        assert_eq!(filled_quantity(&result.fills, "c"), dec!(2));
+    }
+
+    fn matching_policy() -> impl Strategy<Value = MatchingPolicy> {
+        prop_oneof![
+            Just(MatchingPolicy::FIFO),
+            Just(MatchingPolicy::PRO_RATA),
+            Just(MatchingPolicy::HYBRID),
+        ]
+    }
+
+    // a few users, a narrow price range and a mix of order options so orders keep crossing each other
+    fn random_order() -> impl Strategy<Value = (usize, bool, i64, i64, Option<i64>, usize, usize)> {
+        (
+            0..3usize,
+            any::<bool>(),
+            95..105i64,
+            1..20i64,
+            prop::option::of(1..5i64),
+            0..3usize,
+            0..4usize,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn matching_conserves_quantity(
+            matching_policy in matching_policy(),
+            random_orders in prop::collection::vec(random_order(), 1..60),
+        ) {
+            let mut book = orderbook(matching_policy);
+            let mut quantities: HashMap<String, Decimal> = HashMap::new();
+            let mut filled: HashMap<String, Decimal> = HashMap::new();
+            let mut cancelled: HashMap<String, Decimal> = HashMap::new();
+
+            for (index, random_order) in random_orders.into_iter().enumerate() {
+                let (user, buy, price, quantity, display_quantity, time_in_force, self_trade_prevention) =
+                    random_order;
+                let side = if buy { OrderSide::BUY } else { OrderSide::SELL };
+                let order_id = index.to_string();
+                let mut new_order = order(
+                    &order_id,
+                    &format!("user-{}", user),
+                    side,
+                    Decimal::from(price),
+                    Decimal::from(quantity),
+                );
+                new_order.display_quantity = display_quantity.map(Decimal::from);
+                new_order.time_in_force = match time_in_force {
+                    0 => TimeInForce::GTC,
+                    1 => TimeInForce::IOC,
+                    _ => TimeInForce::FOK,
+                };
+                new_order.self_trade_prevention = match self_trade_prevention {
+                    0 => SelfTradePrevention::CANCEL_NEWEST,
+                    1 => SelfTradePrevention::CANCEL_OLDEST,
+                    2 => SelfTradePrevention::CANCEL_BOTH,
+                    _ => SelfTradePrevention::DECREMENT_AND_CANCEL,
+                };
+                quantities.insert(order_id.clone(), Decimal::from(quantity));
+
+                let resting_before = resting_total(&book);
+                let result = book.process_order(new_order).unwrap();
+
+                // both sides of every trade move the same volume
+                let fills_total: Decimal = result.fills.iter().map(|fill| fill.quantity).sum();
+                prop_assert_eq!(result.executed_quantity, fills_total);
+                prop_assert!(
+                    result.executed_quantity + result.cancelled_quantity <= Decimal::from(quantity)
+                );
+
+                *filled.entry(order_id.clone()).or_default() += result.executed_quantity;
+                *cancelled.entry(order_id.clone()).or_default() += result.cancelled_quantity;
+                for fill in result.fills.iter() {
+                    *filled.entry(fill.order_id.clone()).or_default() += fill.quantity;
+                }
+                let mut resting_cancelled = dec!(0);
+                for (resting_order, cancelled_quantity) in result.cancelled_orders.iter() {
+                    *cancelled.entry(resting_order.order_id.clone()).or_default() +=
+                        cancelled_quantity;
+                    resting_cancelled += cancelled_quantity;
+                }
+
+                // the book loses what the makers filled and what self-trade prevention cancelled, and
+                // gains whatever of the incoming order rested
+                let rested = resting_quantity(&book, &order_id).unwrap_or(dec!(0));
+                prop_assert_eq!(
+                    resting_total(&book),
+                    resting_before - fills_total - resting_cancelled + rested
+                );
+
+                // the book is never left crossed
+                if let (Some(best_bid), Some(best_ask)) = (book.best_bid(), book.best_ask()) {
+                    prop_assert!(best_bid < best_ask);
+                }
+            }
+
+            // every order's filled, cancelled and resting quantities add up to its original quantity
+            for (order_id, quantity) in quantities.iter() {
+                let remaining = resting_quantity(&book, order_id).unwrap_or(dec!(0));
+                let filled = filled.get(order_id).copied().unwrap_or_default();
+                let cancelled = cancelled.get(order_id).copied().unwrap_or_default();
+                prop_assert_eq!(filled + cancelled + remaining, *quantity, "order {}", order_id);
+            }
+
+            // depth shows what is left of the resting orders, icebergs their visible slice
+            let (bids_depth, asks_depth) = book.get_depth();
+            for (depth, levels) in [(bids_depth, &book.bids), (asks_depth, &book.asks)] {
+                for (price, depth_quantity) in depth.iter() {
+                    let visible: Decimal = levels[price]
+                        .iter()
+                        .map(|order| {
+                            order
+                                .visible_quantity
+                                .unwrap_or(order.quantity - order.filled_quantity)
+                        })
+                        .sum();
+                    prop_assert_eq!(*depth_quantity, visible);
+                }
+            }
+        }
+    }
+
+    fn resting_total(orderbook: &OrderBook) -> Decimal {
+        orderbook
+            .bids
+            .values()
+            .chain(orderbook.asks.values())
+            .flatten()
+            .map(|order| order.quantity - order.filled_quantity)
+            .sum()
+    }
+}
@@ -1,5 +1,11 @@
 pub mod engine;
 pub mod error;
+pub mod fees;
+#[cfg(test)]
+mod fixtures;
+pub mod ledger;
+pub mod matching;
 pub mod orderbook;
+pub mod trigger_book;
 pub mod db;
 pub mod ws_stream;
\ No newline at end of file
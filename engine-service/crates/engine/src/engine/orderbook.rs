@@ -1,9 +1,13 @@
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
-use crate::types::engine::{AssetPair, CancelOrder, Fill, Order, OrderSide, ProcessOrderResult};
+use super::matching::matching_algorithm;
+use crate::types::engine::{
+    AssetPair, AuctionTrade, Fill, MarketConfig, Order, OrderSide, OrderStatus, OrderType,
+    ProcessOrderResult, SelfTradePrevention, TimeInForce, TradingState,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderBook {
@@ -11,133 +15,585 @@ pub struct OrderBook {
     pub asks: BTreeMap<Decimal, Vec<Order>>,
     pub asset_pair: AssetPair,
     pub trade_id: i64,
+    pub tick_size: Decimal,
+    pub config: MarketConfig, // order size and precision filters from markets.json
+    pub last_trade_price: Option<Decimal>,
+    pub halted_until: Option<i64>,  // end of a circuit breaker halt
+    pub auction_until: Option<i64>, // when the current auction uncrosses
+    order_index: HashMap<String, (OrderSide, Decimal)>, // order_id -> where the order rests
+    recent_trades: VecDeque<(i64, Decimal)>, // (timestamp, price) of trades inside the circuit breaker window
     last_update_id: i64,
 }
 
 impl OrderBook {
-    pub fn new(asset_pair: AssetPair, trade_id: i64) -> OrderBook {
+    pub fn new(config: MarketConfig, trade_id: i64) -> OrderBook {
         OrderBook {
             asks: BTreeMap::new(),
             bids: BTreeMap::new(),
-            asset_pair,
+            asset_pair: AssetPair {
+                base: config.base_asset.clone(),
+                quote: config.quote_asset.clone(),
+            },
             trade_id,
+            tick_size: Decimal::new(1, config.price_precision),
+            config,
+            last_trade_price: None,
+            halted_until: None,
+            auction_until: None,
+            order_index: HashMap::new(),
+            recent_trades: VecDeque::new(),
             last_update_id: 0,
         }
     }
 
     pub fn ticker(&self) -> String {
-        format!("{:?}_{:?}", self.asset_pair.base, self.asset_pair.quote)
+        format!("{}_{}", self.asset_pair.base, self.asset_pair.quote)
     }
 
-    pub fn process_order(&mut self, mut order: Order) -> ProcessOrderResult {
-        let order_result: ProcessOrderResult;
+    // checks a price (if the order has one) and quantity against the market's filters
+    pub fn check_filters(
+        &self,
+        price: Option<Decimal>,
+        quantity: Decimal,
+    ) -> Result<(), &'static str> {
+        if quantity < self.config.min_order_size {
+            return Err("Quantity is below the minimum order size");
+        }
+        if quantity > self.config.max_order_size {
+            return Err("Quantity is above the maximum order size");
+        }
+        if quantity.normalize().scale() > self.config.quantity_precision {
+            return Err("Quantity has more decimals than the market's quantity precision");
+        }
 
-        match order.side {
-            OrderSide::BUY => {
-                order_result = self.match_asks(&order);
-                order.filled_quantity = order_result.executed_quantity;
-                if order_result.executed_quantity < order.quantity {
-                    self.bids
-                        .entry(order.price)
-                        .and_modify(|orders| orders.push(order.clone())) // If the price exists, append the order
-                        .or_insert(vec![order]);
-                }
-                order_result
+        if let Some(price) = price {
+            if price <= dec!(0) {
+                return Err("Price must be positive");
             }
-            OrderSide::SELL => {
-                order_result = self.match_bids(&order);
-
-                if order_result.executed_quantity < order.quantity {
-                    self.asks
-                        .entry(order.price)
-                        .and_modify(|orders| orders.push(order.clone())) // If the price exists, append the order
-                        .or_insert(vec![order]);
-                }
-                order_result
+            if price.normalize().scale() > self.config.price_precision {
+                return Err("Price is not a multiple of the market's tick size");
             }
         }
+
+        Ok(())
     }
 
-    pub fn match_asks(&mut self, order: &Order) -> ProcessOrderResult {
-        let mut fills: Vec<Fill> = vec![];
-        let mut executed_quantity: Decimal = dec!(0);
-
-        for (_price, asks) in self.asks.iter_mut() {
-            for ask in asks.iter_mut() {
-                if order.price >= ask.price && executed_quantity < order.quantity {
-                    let filled_quantity =
-                        std::cmp::min(ask.quantity - executed_quantity, ask.quantity);
-                    self.trade_id += 1;
-
-                    executed_quantity += filled_quantity;
-                    ask.filled_quantity += filled_quantity;
-
-                    fills.push(Fill {
-                        price: ask.price,
-                        quantity: filled_quantity,
-                        trade_id: self.trade_id,
-                        other_user_id: ask.user_id.clone(),
-                        order_id: ask.order_id.clone(),
+    // furthest price a taker on `side` may trade at - the band is centred on the last trade,
+    // or on the reference price before the market has traded
+    pub fn band_limit(&self, side: &OrderSide) -> Option<Decimal> {
+        let band_percent = self.config.price_band_percent?;
+        let reference_price = self.last_trade_price.or(self.config.reference_price)?;
+
+        Some(match side {
+            OrderSide::BUY => reference_price * (dec!(100) + band_percent) / dec!(100),
+            OrderSide::SELL => reference_price * (dec!(100) - band_percent) / dec!(100),
+        })
+    }
+
+    fn within_band(band_limit: Option<Decimal>, side: &OrderSide, price: Decimal) -> bool {
+        match (band_limit, side) {
+            (None, _) => true,
+            (Some(band_limit), OrderSide::BUY) => price <= band_limit,
+            (Some(band_limit), OrderSide::SELL) => price >= band_limit,
+        }
+    }
+
+    // keeps the trade prices inside the circuit breaker window and, once they moved more than the
+    // breaker allows, returns how far they moved in percent and when the halt it trips ends
+    pub fn record_trades(&mut self, fills: &[Fill], now: i64) -> Option<(Decimal, i64)> {
+        let breaker_percent = self.config.circuit_breaker_percent?;
+        let window_secs = self.config.circuit_breaker_window_secs?;
+        let halt_secs = self.config.circuit_breaker_halt_secs?;
+
+        for fill in fills.iter() {
+            self.recent_trades.push_back((now, fill.price));
+        }
+        while let Some((timestamp, _)) = self.recent_trades.front() {
+            if *timestamp >= now - window_secs * 1000 {
+                break;
+            }
+            self.recent_trades.pop_front();
+        }
+
+        let low = self.recent_trades.iter().map(|(_, price)| *price).min()?;
+        let high = self.recent_trades.iter().map(|(_, price)| *price).max()?;
+        if low <= dec!(0) {
+            return None;
+        }
+
+        let move_percent = (high - low) / low * dec!(100);
+        if move_percent > breaker_percent {
+            self.recent_trades.clear();
+            return Some((move_percent, now + halt_secs * 1000));
+        }
+
+        None
+    }
+
+    pub fn process_order(&mut self, mut order: Order) -> Result<ProcessOrderResult, &'static str> {
+        // auctions only collect orders that can wait for the uncross
+        if self.config.trading_state == TradingState::AUCTION {
+            return match (&order.order_type, &order.time_in_force, order.post_only) {
+                (OrderType::LIMIT, TimeInForce::GTC, false)
+                | (OrderType::LIMIT, TimeInForce::GTD, false) => {
+                    order.visible_quantity = Self::first_slice(&order);
+                    self.insert_order(order);
+
+                    Ok(ProcessOrderResult {
+                        executed_quantity: dec!(0),
+                        fills: vec![],
+                        cancelled_quantity: dec!(0),
+                        cancelled_orders: vec![],
+                        filled_orders: vec![],
                     })
                 }
+                _ => Err("Market is in an auction - only GTC and GTD limit orders are accepted"),
+            };
+        }
+
+        if order.post_only {
+            order.price =
+                self.post_only_price(&order.side, order.price, order.post_only_reprice)?;
+        }
+
+        // fill or kill orders are checked against the book before anything is matched - liquidity of the
+        // order's own user only counts as far as its self-trade prevention lets it trade
+        if let TimeInForce::FOK = order.time_in_force {
+            if self.simulate(&order).executed_quantity < order.quantity {
+                return Ok(ProcessOrderResult {
+                    executed_quantity: dec!(0),
+                    fills: vec![],
+                    cancelled_quantity: order.quantity,
+                    cancelled_orders: vec![],
+                    filled_orders: vec![],
+                });
             }
+        }
 
-            // Remove asks that have been completely filled
-            asks.retain(|ask| ask.filled_quantity < ask.quantity);
+        let mut order_result = match order.side {
+            OrderSide::BUY => self.match_asks(&order),
+            OrderSide::SELL => self.match_bids(&order),
+        };
+        order.filled_quantity = order_result.executed_quantity;
+        if order.filled_quantity > dec!(0) {
+            order.order_status = OrderStatus::PartiallyFilled;
+        }
+
+        for fill in order_result.fills.iter() {
+            self.unindex_if_removed(&fill.order_id);
+        }
+        for (cancelled_order, _) in order_result.cancelled_orders.iter() {
+            self.unindex_if_removed(&cancelled_order.order_id);
+        }
+
+        if let Some(fill) = order_result.fills.last() {
+            self.last_trade_price = Some(fill.price);
+        }
+
+        if order_result.executed_quantity + order_result.cancelled_quantity < order.quantity {
+            // an order stopped at the price band edge would cross the book if it rested
+            let crosses_book = match order.side {
+                OrderSide::BUY => self
+                    .best_ask()
+                    .is_some_and(|best_ask| order.price >= best_ask),
+                OrderSide::SELL => self
+                    .best_bid()
+                    .is_some_and(|best_bid| order.price <= best_bid),
+            };
+
+            // only GTC and GTD limit orders rest on the book - whatever is left of market, IOC and FOK orders is cancelled
+            match (&order.order_type, &order.time_in_force) {
+                (OrderType::LIMIT, TimeInForce::GTC) | (OrderType::LIMIT, TimeInForce::GTD)
+                    if !crosses_book =>
+                {
+                    // self-trade prevention may have decremented the order before it rests
+                    order.quantity -= order_result.cancelled_quantity;
+                    order.visible_quantity = Self::first_slice(&order);
+                    self.insert_order(order);
+                }
+                _ => {
+                    order_result.cancelled_quantity =
+                        order.quantity - order_result.executed_quantity;
+                }
+            }
         }
 
-        ProcessOrderResult {
-            fills,
-            executed_quantity,
+        Ok(order_result)
+    }
+
+    // price the auction would uncross at right now and the volume it would execute. the price maximises
+    // the executed volume, then minimises the quantity left unmatched at it, then is closest to the last trade
+    pub fn indicative_uncross(&self) -> Option<(Decimal, Decimal)> {
+        let reference_price = self.last_trade_price.or(self.config.reference_price);
+        let mut uncross: Option<(Decimal, Decimal, Decimal)> = None; // (price, volume, imbalance)
+
+        for price in self.bids.keys().chain(self.asks.keys()) {
+            let demand = self
+                .bids
+                .range(*price..)
+                .flat_map(|(_, orders)| orders.iter())
+                .fold(dec!(0), |acc, order| {
+                    acc + order.quantity - order.filled_quantity
+                });
+            let supply = self
+                .asks
+                .range(..=*price)
+                .flat_map(|(_, orders)| orders.iter())
+                .fold(dec!(0), |acc, order| {
+                    acc + order.quantity - order.filled_quantity
+                });
+
+            let volume = std::cmp::min(demand, supply);
+            if volume <= dec!(0) {
+                continue;
+            }
+            let imbalance = (demand - supply).abs();
+
+            let better = match uncross {
+                None => true,
+                Some((best_price, best_volume, best_imbalance)) => {
+                    volume > best_volume
+                        || (volume == best_volume && imbalance < best_imbalance)
+                        || (volume == best_volume
+                            && imbalance == best_imbalance
+                            && reference_price.is_some_and(|reference_price| {
+                                (*price - reference_price).abs()
+                                    < (best_price - reference_price).abs()
+                            }))
+                }
+            };
+            if better {
+                uncross = Some((*price, volume, imbalance));
+            }
         }
+
+        uncross.map(|(price, volume, _)| (price, volume))
     }
 
-    pub fn match_bids(&mut self, order: &Order) -> ProcessOrderResult {
-        let mut fills: Vec<Fill> = vec![];
-        let mut executed_quantity: Decimal = dec!(0);
-
-        for (_price, bids) in self.bids.iter_mut().rev() {
-            for bid in bids.iter_mut() {
-                if order.price <= bid.price && executed_quantity < order.quantity {
-                    let filled_quantity =
-                        std::cmp::min(bid.quantity - executed_quantity, bid.quantity);
-                    self.trade_id += 1;
-
-                    executed_quantity += filled_quantity;
-                    bid.filled_quantity += filled_quantity;
-
-                    fills.push(Fill {
-                        price: bid.price,
-                        quantity: filled_quantity,
-                        trade_id: self.trade_id,
-                        other_user_id: bid.user_id.clone(),
-                        order_id: bid.order_id.clone(),
-                    })
+    // ends an auction - bids at or above the clearing price are matched with asks at or below it, in
+    // price then time priority, and every match trades at the clearing price. also returns the orders
+    // self-trade prevention cancelled or decremented, with the quantity it took off each
+    pub fn uncross(&mut self) -> (Vec<AuctionTrade>, Vec<(Order, Decimal)>) {
+        let mut trades: Vec<AuctionTrade> = Vec::new();
+        let mut cancelled_orders: Vec<(Order, Decimal)> = Vec::new();
+        let price = match self.indicative_uncross() {
+            Some((price, _)) => price,
+            None => return (trades, cancelled_orders),
+        };
+
+        loop {
+            let bid_price = match self.best_bid() {
+                Some(bid_price) if bid_price >= price => bid_price,
+                _ => break,
+            };
+            let ask_price = match self.best_ask() {
+                Some(ask_price) if ask_price <= price => ask_price,
+                _ => break,
+            };
+            let (bids, asks) = match (self.bids.get_mut(&bid_price), self.asks.get_mut(&ask_price))
+            {
+                (Some(bids), Some(asks)) => (bids, asks),
+                _ => break,
+            };
+
+            let bid_quantity = bids[0].quantity - bids[0].filled_quantity;
+            let ask_quantity = asks[0].quantity - asks[0].filled_quantity;
+            let quantity = std::cmp::min(bid_quantity, ask_quantity);
+
+            // a user's bid and ask never trade with each other - the newer of the two applies its
+            // self-trade prevention, as it would have had it come in while the market was open
+            if bids[0].user_id == asks[0].user_id {
+                let bid_is_newer = bids[0].timestamp >= asks[0].timestamp;
+                let self_trade_prevention = if bid_is_newer {
+                    bids[0].self_trade_prevention.clone()
+                } else {
+                    asks[0].self_trade_prevention.clone()
+                };
+                let (bid_cancelled, ask_cancelled) = match (self_trade_prevention, bid_is_newer) {
+                    (SelfTradePrevention::CANCEL_NEWEST, true)
+                    | (SelfTradePrevention::CANCEL_OLDEST, false) => (bid_quantity, dec!(0)),
+                    (SelfTradePrevention::CANCEL_NEWEST, false)
+                    | (SelfTradePrevention::CANCEL_OLDEST, true) => (dec!(0), ask_quantity),
+                    (SelfTradePrevention::CANCEL_BOTH, _) => (bid_quantity, ask_quantity),
+                    (SelfTradePrevention::DECREMENT_AND_CANCEL, _) => (quantity, quantity),
+                };
+
+                for (orders, cancelled_quantity) in [(bids, bid_cancelled), (asks, ask_cancelled)] {
+                    if cancelled_quantity <= dec!(0) {
+                        continue;
+                    }
+
+                    if cancelled_quantity < orders[0].quantity - orders[0].filled_quantity {
+                        orders[0].quantity -= cancelled_quantity;
+                        orders[0].visible_quantity = Self::first_slice(&orders[0]);
+                        cancelled_orders.push((orders[0].clone(), cancelled_quantity));
+                    } else {
+                        let mut order = orders.remove(0);
+                        self.order_index.remove(&order.order_id);
+                        order.order_status = OrderStatus::Cancelled;
+                        cancelled_orders.push((order, cancelled_quantity));
+                    }
+                }
+                continue;
+            }
+
+            self.trade_id += 1;
+
+            for order in [&mut bids[0], &mut asks[0]] {
+                order.filled_quantity += quantity;
+                order.visible_quantity = Self::first_slice(order);
+                order.order_status = if order.filled_quantity >= order.quantity {
+                    OrderStatus::Filled
+                } else {
+                    OrderStatus::PartiallyFilled
+                };
+            }
+
+            trades.push(AuctionTrade {
+                buy_order: bids[0].clone(),
+                sell_order: asks[0].clone(),
+                price,
+                quantity,
+                trade_id: self.trade_id,
+            });
+
+            for orders in [bids, asks] {
+                if orders[0].filled_quantity >= orders[0].quantity {
+                    let order = orders.remove(0);
+                    self.order_index.remove(&order.order_id);
                 }
             }
+        }
 
-            // Remove bids that have been completely filled
-            bids.retain(|bid| bid.filled_quantity < bid.quantity);
+        if !trades.is_empty() {
+            self.last_trade_price = Some(price);
         }
 
-        ProcessOrderResult {
-            fills,
-            executed_quantity,
+        (trades, cancelled_orders)
+    }
+
+    pub fn best_bid(&self) -> Option<Decimal> {
+        self.bids
+            .iter()
+            .rev()
+            .find(|(_, orders)| !orders.is_empty())
+            .map(|(price, _)| *price)
+    }
+
+    pub fn best_ask(&self) -> Option<Decimal> {
+        self.asks
+            .iter()
+            .find(|(_, orders)| !orders.is_empty())
+            .map(|(price, _)| *price)
+    }
+
+    // price a post-only order can rest at without taking liquidity - crossing orders are rejected,
+    // or moved one tick behind the opposite touch when `reprice` is set
+    pub fn post_only_price(
+        &self,
+        side: &OrderSide,
+        price: Decimal,
+        reprice: bool,
+    ) -> Result<Decimal, &'static str> {
+        let repriced = match side {
+            OrderSide::BUY => match self.best_ask() {
+                Some(best_ask) if price >= best_ask => best_ask - self.tick_size,
+                _ => return Ok(price),
+            },
+            OrderSide::SELL => match self.best_bid() {
+                Some(best_bid) if price <= best_bid => best_bid + self.tick_size,
+                _ => return Ok(price),
+            },
+        };
+
+        if !reprice {
+            return Err("Post-only order would take liquidity");
         }
+
+        if repriced <= dec!(0) {
+            return Err("Post-only order cannot be repriced below zero");
+        }
+
+        Ok(repriced)
     }
 
-    pub fn get_open_order(&self, user_id: String, order_id: String) -> Result<&Order, ()> {
-        let order = self
-            .bids
+    // visible slice an iceberg order starts with when it rests on the book
+    fn first_slice(order: &Order) -> Option<Decimal> {
+        order.display_quantity.map(|display_quantity| {
+            std::cmp::min(display_quantity, order.quantity - order.filled_quantity)
+        })
+    }
+
+    // market orders cross every price level, limit orders only cross levels at or better than their price
+    fn crosses(order: &Order, price: Decimal) -> bool {
+        match (&order.order_type, &order.side) {
+            (OrderType::MARKET, _) => true,
+            (_, OrderSide::BUY) => order.price >= price,
+            (_, OrderSide::SELL) => order.price <= price,
+        }
+    }
+
+    // what matching the order against the book would do right now - the market's algorithm and the order's
+    // self-trade prevention run on copies of the levels it reaches, so the book is left untouched
+    pub fn simulate(&self, order: &Order) -> ProcessOrderResult {
+        let levels: Box<dyn Iterator<Item = (&Decimal, &Vec<Order>)>> = match order.side {
+            OrderSide::BUY => Box::new(self.asks.iter()),
+            OrderSide::SELL => Box::new(self.bids.iter().rev()),
+        };
+
+        let band_limit = self.band_limit(&order.side);
+        let matching = matching_algorithm(&self.config.matching_policy);
+        let lot_size = Decimal::new(1, self.config.quantity_precision);
+        let mut trade_id = self.trade_id;
+        let mut order_result = ProcessOrderResult {
+            executed_quantity: dec!(0),
+            fills: vec![],
+            cancelled_quantity: dec!(0),
+            cancelled_orders: vec![],
+            filled_orders: vec![],
+        };
+
+        for (price, resting_orders) in levels {
+            let mut resting_orders = resting_orders.clone();
+            if !Self::crosses(order, *price)
+                || !Self::within_band(band_limit, &order.side, *price)
+                || !matching.match_level(
+                    order,
+                    &mut resting_orders,
+                    &mut trade_id,
+                    lot_size,
+                    &mut order_result,
+                )
+            {
+                break;
+            }
+        }
+
+        order_result
+    }
+
+    // quote amount the fills matching would make for a market buy cost, so the lock covers what the order
+    // pays once the price band and its self-trade prevention have applied
+    pub fn market_buy_cost(&self, order: &Order) -> Decimal {
+        self.simulate(order)
+            .fills
+            .iter()
+            .map(|fill| fill.price * fill.quantity)
+            .sum()
+    }
+
+    // base quantity a quote amount buys for `order`, taken off the fills matching would make so the price
+    // band and self-trade prevention apply. whole lots only, and never more than the amount costs
+    pub fn market_buy_quantity(&self, order: &Order, quote_quantity: Decimal) -> Decimal {
+        let lot_size = Decimal::new(1, self.config.quantity_precision);
+        let mut sweep = order.clone();
+        sweep.quantity = self
+            .asks
             .values()
-            .chain(self.asks.values()) // Combine bids and asks
-            .flat_map(|orders| orders.iter()) // Flatten the Vec<Order> for each price level
-            .find(|order| order.user_id == user_id && order.order_id == order_id);
+            .flatten()
+            .map(|ask| ask.quantity - ask.filled_quantity)
+            .sum();
+
+        let mut remaining_quote = quote_quantity;
+        let mut quantity = dec!(0);
+        for fill in self.simulate(&sweep).fills {
+            let affordable_quantity = (remaining_quote / fill.price)
+                .round_dp_with_strategy(self.config.quantity_precision, RoundingStrategy::ToZero);
+            let fill_quantity = std::cmp::min(fill.quantity, affordable_quantity);
+            quantity += fill_quantity;
+            remaining_quote -= fill_quantity * fill.price;
+            if fill_quantity < fill.quantity {
+                break;
+            }
+        }
+
+        // a smaller order can be allocated differently, so the quantity is checked against what it costs
+        while quantity > dec!(0) {
+            sweep.quantity = quantity;
+            if self.market_buy_cost(&sweep) <= quote_quantity {
+                break;
+            }
+            quantity -= lot_size;
+        }
+
+        quantity
+    }
+
+    pub fn match_asks(&mut self, order: &Order) -> ProcessOrderResult {
+        let band_limit = self.band_limit(&order.side);
+        let matching = matching_algorithm(&self.config.matching_policy);
+        let lot_size = Decimal::new(1, self.config.quantity_precision);
+        let mut order_result = ProcessOrderResult {
+            executed_quantity: dec!(0),
+            fills: vec![],
+            cancelled_quantity: dec!(0),
+            cancelled_orders: vec![],
+            filled_orders: vec![],
+        };
 
-        match order {
-            Some(order) => Ok(order),
-            None => Err(()),
+        for (price, asks) in self.asks.iter_mut() {
+            if !Self::crosses(order, *price)
+                || !Self::within_band(band_limit, &order.side, *price)
+                || !matching.match_level(
+                    order,
+                    asks,
+                    &mut self.trade_id,
+                    lot_size,
+                    &mut order_result,
+                )
+            {
+                break;
+            }
         }
+
+        order_result
+    }
+
+    pub fn match_bids(&mut self, order: &Order) -> ProcessOrderResult {
+        let band_limit = self.band_limit(&order.side);
+        let matching = matching_algorithm(&self.config.matching_policy);
+        let lot_size = Decimal::new(1, self.config.quantity_precision);
+        let mut order_result = ProcessOrderResult {
+            executed_quantity: dec!(0),
+            fills: vec![],
+            cancelled_quantity: dec!(0),
+            cancelled_orders: vec![],
+            filled_orders: vec![],
+        };
+
+        for (price, bids) in self.bids.iter_mut().rev() {
+            if !Self::crosses(order, *price)
+                || !Self::within_band(band_limit, &order.side, *price)
+                || !matching.match_level(
+                    order,
+                    bids,
+                    &mut self.trade_id,
+                    lot_size,
+                    &mut order_result,
+                )
+            {
+                break;
+            }
+        }
+
+        order_result
+    }
+
+    pub fn get_open_order(&self, user_id: String, order_id: String) -> Result<&Order, ()> {
+        let (side, price) = self.order_index.get(&order_id).ok_or(())?;
+        let orders_map = match side {
+            OrderSide::BUY => &self.bids,
+            OrderSide::SELL => &self.asks,
+        };
+
+        orders_map
+            .get(price)
+            .and_then(|orders| {
+                orders
+                    .iter()
+                    .find(|order| order.user_id == user_id && order.order_id == order_id)
+            })
+            .ok_or(())
     }
 
     pub fn get_open_orders(&mut self, user_id: String) -> Vec<&Order> {
@@ -149,61 +605,153 @@ impl OrderBook {
             .collect()
     }
 
-    pub fn cancel_order(&mut self, cancel_order: CancelOrder) -> Result<Order, ()> {
-        let cancel = |orders_map: &mut BTreeMap<Decimal, Vec<Order>>| {
-            if let Some(orders) = orders_map.get_mut(&cancel_order.price) {
-                if let Some(index) = orders
-                    .iter()
-                    .position(|order| order.order_id == cancel_order.order_id)
-                {
-                    let order = orders.get(index).unwrap().clone();
-                    orders.remove(index);
-                    Ok(order)
+    pub fn cancel_order(&mut self, user_id: &str, order_id: &str) -> Result<Order, ()> {
+        let (side, price) = self.order_index.get(order_id).cloned().ok_or(())?;
+        let orders_map = match side {
+            OrderSide::BUY => &mut self.bids,
+            OrderSide::SELL => &mut self.asks,
+        };
+
+        let orders = orders_map.get_mut(&price).ok_or(())?;
+        let index = orders
+            .iter()
+            .position(|order| order.user_id == user_id && order.order_id == order_id)
+            .ok_or(())?;
+
+        self.order_index.remove(order_id);
+        Ok(orders.remove(index))
+    }
+
+    // sets a resting order's price and total quantity. a quantity reduction keeps the order's place in the queue,
+    // any other change moves it to the back of its (new) price level
+    pub fn modify_order(
+        &mut self,
+        user_id: &str,
+        order_id: &str,
+        price: Decimal,
+        quantity: Decimal,
+    ) -> Result<Order, &'static str> {
+        let (side, current_price) = self
+            .get_open_order(user_id.to_string(), order_id.to_string())
+            .map(|order| (order.side.clone(), order.price))
+            .map_err(|_| "Order not found")?;
+
+        let orders_map = match side {
+            OrderSide::BUY => &mut self.bids,
+            OrderSide::SELL => &mut self.asks,
+        };
+        let orders = orders_map
+            .get_mut(&current_price)
+            .ok_or("Order not found")?;
+        let index = orders
+            .iter()
+            .position(|order| order.order_id == order_id)
+            .ok_or("Order not found")?;
+
+        if price == current_price && quantity <= orders[index].quantity {
+            let order = &mut orders[index];
+            order.quantity = quantity;
+            order.visible_quantity = order.visible_quantity.map(|visible_quantity| {
+                std::cmp::min(visible_quantity, quantity - order.filled_quantity)
+            });
+
+            return Ok(order.clone());
+        }
+
+        let mut order = orders.remove(index);
+        order.price = price;
+        order.quantity = quantity;
+        order.visible_quantity = Self::first_slice(&order);
+        self.insert_order(order.clone());
+
+        Ok(order)
+    }
+
+    // removes the user's orders, optionally only on one side, and returns them
+    pub fn cancel_all_orders(&mut self, user_id: &str, side: Option<&OrderSide>) -> Vec<Order> {
+        let mut cancelled_orders: Vec<Order> = Vec::new();
+
+        for orders in self.bids.values_mut().chain(self.asks.values_mut()) {
+            orders.retain(|order| {
+                if order.user_id == user_id && side.is_none_or(|side| order.side == *side) {
+                    cancelled_orders.push(order.clone());
+                    false
                 } else {
-                    Err(())
+                    true
                 }
-            } else {
-                Err(())
-            }
-        };
+            });
+        }
 
-        match cancel_order.side {
-            OrderSide::BUY => cancel(&mut self.bids),
-            OrderSide::SELL => cancel(&mut self.asks),
+        for order in cancelled_orders.iter() {
+            self.order_index.remove(&order.order_id);
         }
+
+        cancelled_orders
     }
 
-    pub fn cancel_all_orders(&mut self, user_id: String) -> Vec<&Order> {
-        self.bids.values_mut().for_each(|orders| {
-            orders.retain(|order| order.user_id != user_id);
-        });
+    // removes GTD orders whose expiry has passed and returns them
+    pub fn remove_expired_orders(&mut self, now: i64) -> Vec<Order> {
+        let mut expired_orders: Vec<Order> = Vec::new();
 
-        self.asks.values_mut().for_each(|orders| {
-            orders.retain(|order| order.user_id != user_id);
-        });
+        for orders in self.bids.values_mut().chain(self.asks.values_mut()) {
+            orders.retain(|order| match order.expires_at {
+                Some(expires_at) if expires_at <= now => {
+                    expired_orders.push(order.clone());
+                    false
+                }
+                _ => true,
+            });
+        }
 
-        self.get_open_orders(user_id)
+        for order in expired_orders.iter() {
+            self.order_index.remove(&order.order_id);
+        }
+
+        expired_orders
     }
 
-    pub fn restore_order(&mut self, order: Order) {
+    pub fn restore_order(&mut self, mut order: Order) {
         // Only restore orders that are not fully filled
         if order.filled_quantity >= order.quantity {
             return;
         }
+        order.visible_quantity = Self::first_slice(&order);
 
-        match order.side {
-            OrderSide::BUY => {
-                self.bids
-                    .entry(order.price)
-                    .and_modify(|orders| orders.push(order.clone()))
-                    .or_insert(vec![order]);
-            }
-            OrderSide::SELL => {
-                self.asks
-                    .entry(order.price)
-                    .and_modify(|orders| orders.push(order.clone()))
-                    .or_insert(vec![order]);
-            }
+        self.insert_order(order);
+    }
+
+    // appends the order to the back of its price level and indexes it
+    fn insert_order(&mut self, order: Order) {
+        self.order_index
+            .insert(order.order_id.clone(), (order.side.clone(), order.price));
+
+        let orders_map = match order.side {
+            OrderSide::BUY => &mut self.bids,
+            OrderSide::SELL => &mut self.asks,
+        };
+
+        orders_map
+            .entry(order.price)
+            .and_modify(|orders| orders.push(order.clone())) // If the price exists, append the order
+            .or_insert(vec![order]);
+    }
+
+    // drops an order from the index once matching has taken it off the book
+    fn unindex_if_removed(&mut self, order_id: &str) {
+        let (side, price) = match self.order_index.get(order_id) {
+            Some(location) => location,
+            None => return,
+        };
+        let orders_map = match side {
+            OrderSide::BUY => &self.bids,
+            OrderSide::SELL => &self.asks,
+        };
+
+        let resting = orders_map
+            .get(price)
+            .is_some_and(|orders| orders.iter().any(|order| order.order_id == order_id));
+        if !resting {
+            self.order_index.remove(order_id);
         }
     }
 
@@ -211,19 +759,23 @@ impl OrderBook {
         let mut bids_depth: Vec<(Decimal, Decimal)> = Vec::new();
         let mut asks_depth: Vec<(Decimal, Decimal)> = Vec::new();
 
-        // Aggregate quantities for each price level in bids
+        // Aggregate remaining quantities for each price level in bids - icebergs only count their visible slice
         for (price, orders) in self.bids.iter() {
-            let total_quantity = orders
-                .iter()
-                .fold(Decimal::ZERO, |acc, order| acc + order.quantity);
+            let total_quantity = orders.iter().fold(Decimal::ZERO, |acc, order| {
+                acc + order
+                    .visible_quantity
+                    .unwrap_or(order.quantity - order.filled_quantity)
+            });
             bids_depth.push((*price, total_quantity));
         }
 
-        // Aggregate quantities for each price level in asks
+        // Aggregate remaining quantities for each price level in asks
         for (price, orders) in self.asks.iter() {
-            let total_quantity = orders
-                .iter()
-                .fold(Decimal::ZERO, |acc, order| acc + order.quantity);
+            let total_quantity = orders.iter().fold(Decimal::ZERO, |acc, order| {
+                acc + order
+                    .visible_quantity
+                    .unwrap_or(order.quantity - order.filled_quantity)
+            });
             asks_depth.push((*price, total_quantity));
         }
 
@@ -0,0 +1,158 @@
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+
+use crate::types::engine::{Order, OrderSide, OrderType};
+
+// stop, take profit and trailing stop orders of a single market, waiting for a trade to cross their trigger price
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TriggerBook {
+    pub orders: Vec<Order>,
+}
+
+impl TriggerBook {
+    pub fn new() -> TriggerBook {
+        TriggerBook { orders: Vec::new() }
+    }
+
+    pub fn add_order(&mut self, order: Order) {
+        self.orders.push(order);
+    }
+
+    // buy stops and sell take profits fire when price rises to the trigger,
+    // sell stops and buy take profits fire when price falls to it - trailing stops fire like stops
+    pub fn is_triggered(order: &Order, low_price: Decimal, high_price: Decimal) -> bool {
+        let trigger_price = match order.trigger_price {
+            Some(trigger_price) => trigger_price,
+            None => return false,
+        };
+
+        match (&order.order_type, &order.side) {
+            (
+                OrderType::STOP_MARKET
+                | OrderType::STOP_LIMIT
+                | OrderType::TRAILING_STOP_MARKET
+                | OrderType::TRAILING_STOP_LIMIT,
+                OrderSide::BUY,
+            )
+            | (OrderType::TAKE_PROFIT_MARKET | OrderType::TAKE_PROFIT_LIMIT, OrderSide::SELL) => {
+                high_price >= trigger_price
+            }
+            (
+                OrderType::STOP_MARKET
+                | OrderType::STOP_LIMIT
+                | OrderType::TRAILING_STOP_MARKET
+                | OrderType::TRAILING_STOP_LIMIT,
+                OrderSide::SELL,
+            )
+            | (OrderType::TAKE_PROFIT_MARKET | OrderType::TAKE_PROFIT_LIMIT, OrderSide::BUY) => {
+                low_price <= trigger_price
+            }
+            _ => false,
+        }
+    }
+
+    // trigger level of a trailing stop following `price` - sells trail below it, buys above it
+    pub fn trailing_trigger_price(
+        order: &Order,
+        price: Decimal,
+        tick_size: Decimal,
+    ) -> Option<Decimal> {
+        let distance = match (order.trailing_offset, order.trailing_percent) {
+            (Some(trailing_offset), _) => trailing_offset,
+            (None, Some(trailing_percent)) => price * trailing_percent / dec!(100),
+            (None, None) => return None,
+        };
+
+        let trigger_price = match order.side {
+            OrderSide::BUY => price + distance,
+            OrderSide::SELL => price - distance,
+        };
+
+        Some(trigger_price.round_dp(tick_size.scale()))
+    }
+
+    // moves trailing stops after a trade at `price` - triggers only ever move in the order's favour,
+    // and limit prices move with them so the gap to the trigger stays the same. returns the moved orders
+    pub fn trail_orders(&mut self, price: Decimal, tick_size: Decimal) -> Vec<Order> {
+        let mut trailed_orders: Vec<Order> = Vec::new();
+
+        for order in self.orders.iter_mut() {
+            if !order.order_type.is_trailing() {
+                continue;
+            }
+
+            let (trigger_price, new_trigger_price) = match (
+                order.trigger_price,
+                Self::trailing_trigger_price(order, price, tick_size),
+            ) {
+                (Some(trigger_price), Some(new_trigger_price)) => {
+                    (trigger_price, new_trigger_price)
+                }
+                _ => continue,
+            };
+
+            let moved = match order.side {
+                OrderSide::BUY => new_trigger_price < trigger_price,
+                OrderSide::SELL => new_trigger_price > trigger_price,
+            };
+
+            if moved {
+                if let OrderType::TRAILING_STOP_LIMIT = order.order_type {
+                    order.price += new_trigger_price - trigger_price;
+                }
+                order.trigger_price = Some(new_trigger_price);
+                trailed_orders.push(order.clone());
+            }
+        }
+
+        trailed_orders
+    }
+
+    // removes and returns, in placement order, every order that trades between low and high would fire
+    pub fn take_triggered_orders(&mut self, low_price: Decimal, high_price: Decimal) -> Vec<Order> {
+        let mut triggered_orders: Vec<Order> = Vec::new();
+
+        self.orders.retain(|order| {
+            if Self::is_triggered(order, low_price, high_price) {
+                triggered_orders.push(order.clone());
+                false
+            } else {
+                true
+            }
+        });
+
+        triggered_orders
+    }
+
+    pub fn get_open_order(&self, user_id: &str, order_id: &str) -> Option<&Order> {
+        self.orders
+            .iter()
+            .find(|order| order.user_id == user_id && order.order_id == order_id)
+    }
+
+    pub fn get_open_orders(&self, user_id: &str) -> Vec<&Order> {
+        self.orders
+            .iter()
+            .filter(|order| order.user_id == user_id)
+            .collect()
+    }
+
+    pub fn cancel_order(&mut self, user_id: &str, order_id: &str) -> Option<Order> {
+        let index = self
+            .orders
+            .iter()
+            .position(|order| order.user_id == user_id && order.order_id == order_id)?;
+
+        Some(self.orders.remove(index))
+    }
+
+    pub fn cancel_all_orders(&mut self, user_id: &str, side: Option<&OrderSide>) -> Vec<Order> {
+        let (cancelled_orders, orders) = self.orders.drain(..).partition(|order| {
+            order.user_id == user_id && side.is_none_or(|side| order.side == *side)
+        });
+        self.orders = orders;
+
+        cancelled_orders
+    }
+}
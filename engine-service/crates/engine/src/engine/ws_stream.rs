@@ -1,6 +1,6 @@
 use super::engine::Engine;
 use crate::types::{
-    engine::{Fill, OrderSide},
+    engine::{Fill, MarketConfig, Order, OrderSide, TradingState},
     ws_stream::WsResponse,
 };
 use async_trait::async_trait;
@@ -26,6 +26,47 @@ pub trait WsStreamUpdates {
         fills: &Vec<Fill>,
         redis_conn: &RedisManager,
     );
+
+    async fn publish_ws_order_update(
+        &self,
+        market: String,
+        order: &Order,
+        redis_conn: &RedisManager,
+    );
+
+    async fn publish_ws_markets_update(&self, redis_conn: &RedisManager);
+
+    async fn publish_ws_auction(
+        &self,
+        market: String,
+        indicative_uncross: Option<(Decimal, Decimal)>,
+        auction_until: i64,
+        redis_conn: &RedisManager,
+    );
+
+    async fn publish_ws_depth_levels(
+        &self,
+        market: String,
+        bid_prices: &[Decimal],
+        ask_prices: &[Decimal],
+        redis_conn: &RedisManager,
+    );
+
+    async fn publish_ws_circuit_breaker(
+        &self,
+        market: String,
+        price: Decimal,
+        move_percent: Decimal,
+        halted_until: i64,
+        redis_conn: &RedisManager,
+    );
+
+    async fn publish_ws_trading_state(
+        &self,
+        market: String,
+        trading_state: &TradingState,
+        redis_conn: &RedisManager,
+    );
 }
 
 #[async_trait]
@@ -66,6 +107,232 @@ impl WsStreamUpdates for Engine {
         }
     }
 
+    // order status changes the trade stream does not cover, e.g. cancels - keyed by order id, no user details
+    async fn publish_ws_order_update(
+        &self,
+        market: String,
+        order: &Order,
+        redis_conn: &RedisManager,
+    ) {
+        let stream = format!("order.{}", market);
+        let data = serde_json::json!({
+            "e": "order",
+            "s": market,
+            "i": order.order_id,
+            "S": order.side,
+            "p": order.price,
+            "q": order.quantity,
+            "z": order.filled_quantity,
+            "X": order.order_status,
+            "T": chrono::Utc::now().timestamp_millis(),
+        });
+
+        let ws_response = WsResponse {
+            stream: stream.clone(),
+            data,
+        };
+        let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+        let result = redis_conn
+            .publish(stream.as_str(), ws_response_string)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error publishing to redis: {}", e);
+        }
+    }
+
+    // sent on every stream of the market so subscribers learn about halts whatever they follow
+    async fn publish_ws_trading_state(
+        &self,
+        market: String,
+        trading_state: &TradingState,
+        redis_conn: &RedisManager,
+    ) {
+        for stream_type in ["depth", "trade", "ticker", "order"] {
+            let stream = format!("{}.{}", stream_type, market);
+            let data = serde_json::json!({
+                "e": "tradingState",
+                "s": market,
+                "X": trading_state,
+                "T": chrono::Utc::now().timestamp_millis(),
+            });
+
+            let ws_response = WsResponse {
+                stream: stream.clone(),
+                data,
+            };
+            let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+            let result = redis_conn
+                .publish(stream.as_str(), ws_response_string)
+                .await;
+
+            if let Err(e) = result {
+                eprintln!("Error publishing to redis: {}", e);
+            }
+        }
+    }
+
+    async fn publish_ws_circuit_breaker(
+        &self,
+        market: String,
+        price: Decimal,
+        move_percent: Decimal,
+        halted_until: i64,
+        redis_conn: &RedisManager,
+    ) {
+        let stream = format!("ticker.{}", market);
+        let data = serde_json::json!({
+            "e": "circuitBreaker",
+            "s": market,
+            "p": price,
+            "m": move_percent.round_dp(2),
+            "u": halted_until,
+            "T": chrono::Utc::now().timestamp_millis(),
+        });
+
+        let ws_response = WsResponse {
+            stream: stream.clone(),
+            data,
+        };
+        let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+        let result = redis_conn
+            .publish(stream.as_str(), ws_response_string)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error publishing to redis: {}", e);
+        }
+    }
+
+    // indicative uncross of a running auction - price and volume are null while nothing crosses
+    async fn publish_ws_auction(
+        &self,
+        market: String,
+        indicative_uncross: Option<(Decimal, Decimal)>,
+        auction_until: i64,
+        redis_conn: &RedisManager,
+    ) {
+        let stream = format!("depth.{}", market);
+        let data = serde_json::json!({
+            "e": "auction",
+            "s": market,
+            "p": indicative_uncross.map(|(price, _)| price),
+            "q": indicative_uncross.map(|(_, volume)| volume),
+            "u": auction_until,
+            "T": chrono::Utc::now().timestamp_millis(),
+        });
+
+        let ws_response = WsResponse {
+            stream: stream.clone(),
+            data,
+        };
+        let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+        let result = redis_conn
+            .publish(stream.as_str(), ws_response_string)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error publishing to redis: {}", e);
+        }
+    }
+
+    // current quantity at the given levels, zero for levels that are now empty
+    async fn publish_ws_depth_levels(
+        &self,
+        market: String,
+        bid_prices: &[Decimal],
+        ask_prices: &[Decimal],
+        redis_conn: &RedisManager,
+    ) {
+        let orderbook = match self
+            .orderbooks
+            .iter()
+            .find(|orderbook| orderbook.ticker() == market)
+        {
+            Some(ob) => ob,
+            None => {
+                eprintln!("No matching orderbook found for market: {}", market);
+                return;
+            }
+        };
+
+        let (depth_bids, depth_asks) = orderbook.get_depth();
+        let level = |depth: &Vec<(Decimal, Decimal)>, price: &Decimal| {
+            let quantity = depth
+                .iter()
+                .find(|(level_price, _)| level_price == price)
+                .map(|(_, quantity)| *quantity)
+                .unwrap_or(Decimal::ZERO);
+            (*price, quantity)
+        };
+        let updated_bids: Vec<(Decimal, Decimal)> = bid_prices
+            .iter()
+            .map(|price| level(&depth_bids, price))
+            .collect();
+        let updated_asks: Vec<(Decimal, Decimal)> = ask_prices
+            .iter()
+            .map(|price| level(&depth_asks, price))
+            .collect();
+
+        let stream = format!("depth.{}", market);
+        let data = serde_json::json!({
+            "e": "depth",
+            "s": market,
+            "b": updated_bids,
+            "a": updated_asks,
+        });
+
+        let ws_response = WsResponse {
+            stream: stream.clone(),
+            data,
+        };
+        let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+        let result = redis_conn
+            .publish(stream.as_str(), ws_response_string)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error publishing to redis: {}", e);
+        }
+    }
+
+    // the market registry for ws-stream - stored under the `markets` key for startup and published on change
+    async fn publish_ws_markets_update(&self, redis_conn: &RedisManager) {
+        let stream = "markets".to_string();
+        let markets: Vec<&MarketConfig> = self
+            .orderbooks
+            .iter()
+            .map(|orderbook| &orderbook.config)
+            .collect();
+        let data = serde_json::json!(markets);
+
+        let ws_response = WsResponse {
+            stream: stream.clone(),
+            data,
+        };
+        let ws_response_string = serde_json::to_string(&ws_response).unwrap();
+
+        if let Err(e) = redis_conn
+            .set(stream.as_str(), ws_response_string.clone())
+            .await
+        {
+            eprintln!("Error storing markets in redis: {}", e);
+        }
+
+        let result = redis_conn
+            .publish(stream.as_str(), ws_response_string)
+            .await;
+
+        if let Err(e) = result {
+            eprintln!("Error publishing to redis: {}", e);
+        }
+    }
+
     async fn publish_ws_depth_updates(
         &mut self,
         market: String,
@@ -2,13 +2,14 @@ pub mod engine;
 pub mod order;
 pub mod types;
 pub mod user;
-pub mod user_service;
 
 use engine::engine::Engine;
+use engine::ws_stream::WsStreamUpdates;
 use order::handle_order;
 use redis::{RedisManager, RedisQueues};
 use sqlx_postgres::PostgresDb;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::task;
 use user::handle_user;
@@ -28,6 +29,11 @@ async fn main() {
     // Use Arc and Mutex to safely share engine across tasks
     let engine = Arc::new(Mutex::new(Engine::new()));
     engine.lock().await.init_engine(&pg_pool).await;
+    engine
+        .lock()
+        .await
+        .publish_ws_markets_update(&redis_connection)
+        .await;
     println!("Engine initialized with multiple markets!");
 
     // Spawn a task to handle orders concurrently
@@ -74,7 +80,21 @@ async fn main() {
         }
     });
 
-    // Await both tasks to run concurrently
+    // Spawn a task to expire GTD orders once their expiry has passed
+    let redis_connection_expiry = Arc::clone(&redis_connection); // Arc clone to share the same connection
+    let engine_expiry = Arc::clone(&engine);
+    let expiry_handle = task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let mut engine = engine_expiry.lock().await;
+            engine.expire_orders(&redis_connection_expiry).await;
+            engine.resume_halted_markets(&redis_connection_expiry).await;
+            engine.run_auctions(&redis_connection_expiry).await;
+        }
+    });
+
+    // Await all tasks to run concurrently
     if let Err(e) = orders_handle.await {
         println!("Error in the orders task: {:?}", e);
     }
@@ -82,4 +102,8 @@ async fn main() {
     if let Err(e) = users_handle.await {
         println!("Error in the users task: {:?}", e);
     }
+
+    if let Err(e) = expiry_handle.await {
+        println!("Error in the expiry task: {:?}", e);
+    }
 }
@@ -32,10 +32,12 @@ pub async fn handle_order(
                 let create_order_result = engine.create_order(order, redis_connection).await;
 
                 match create_order_result {
-                    Ok(order_id) => {
+                    Ok((order_id, order_result)) => {
                         let create_order_json = serde_json::json!({
                             "status": "Created Order",
                             "order_id": order_id,
+                            "executed_quantity": order_result.executed_quantity,
+                            "cancelled_quantity": order_result.cancelled_quantity,
                         });
 
                         let create_order_string =
@@ -50,6 +52,7 @@ pub async fn handle_order(
                     Err(str) => {
                         let create_order_json = serde_json::json!({
                             "status": "Failed to Create Order",
+                            "reason": str,
                         });
 
                         let create_order_string =
@@ -64,6 +67,51 @@ pub async fn handle_order(
                 }
             }
 
+            OrderRequests::CreateOcoOrder(oco_order) => {
+                println!("Create OCO Order: {:?}", oco_order);
+                let pubsub_id = oco_order.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let create_oco_order_result =
+                    engine.create_oco_order(oco_order, redis_connection).await;
+
+                match create_oco_order_result {
+                    Ok(oco_order_result) => {
+                        let create_oco_order_json = serde_json::json!({
+                            "status": "Created OCO Order",
+                            "oco_group_id": oco_order_result.oco_group_id,
+                            "limit_order_id": oco_order_result.limit_order_id,
+                            "stop_order_id": oco_order_result.stop_order_id,
+                            "executed_quantity": oco_order_result.executed_quantity,
+                        });
+
+                        let create_oco_order_string =
+                            serde_json::to_string(&create_oco_order_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, create_oco_order_string)
+                            .await;
+
+                        println!("Successfully placed OCO order!")
+                    }
+                    Err(str) => {
+                        let create_oco_order_json = serde_json::json!({
+                            "status": "Failed to Create OCO Order",
+                            "reason": str,
+                        });
+
+                        let create_oco_order_string =
+                            serde_json::to_string(&create_oco_order_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, create_oco_order_string)
+                            .await;
+
+                        println!("OCO order creation failed - {}", str)
+                    }
+                }
+            }
+
             OrderRequests::GetOpenOrder(open_order) => {
                 println!("Get Open Order: {:?}", open_order);
                 let pubsub_id = open_order.pubsub_id.unwrap().to_string();
@@ -102,7 +150,7 @@ pub async fn handle_order(
                 let pubsub_id = cancel_order.pubsub_id.unwrap().to_string();
                 let pubsub_id_ref = pubsub_id.as_str();
 
-                let cancel_order_result = engine.cancel_order(cancel_order);
+                let cancel_order_result = engine.cancel_order(cancel_order, redis_connection).await;
 
                 match cancel_order_result {
                     Ok(cancel_order_id) => {
@@ -135,6 +183,285 @@ pub async fn handle_order(
                 }
             }
 
+            OrderRequests::ModifyOrder(modify_order) => {
+                println!("Modify Order: {:?}", modify_order);
+                let pubsub_id = modify_order.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let modify_order_result = engine.modify_order(modify_order, redis_connection).await;
+
+                match modify_order_result {
+                    Ok(order) => {
+                        let modify_order_json = serde_json::json!({
+                            "status": "Modified Order",
+                            "order": order,
+                        });
+
+                        let modify_order_string =
+                            serde_json::to_string(&modify_order_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, modify_order_string)
+                            .await;
+                        println!("Successfully modified order!")
+                    }
+                    Err(str) => {
+                        let modify_order_json = serde_json::json!({
+                            "status": "Failed to Modify Order",
+                            "reason": str,
+                        });
+
+                        let modify_order_string =
+                            serde_json::to_string(&modify_order_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, modify_order_string)
+                            .await;
+                        println!("Order modification failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::AddMarket(add_market) => {
+                println!("Add Market: {:?}", add_market);
+                let pubsub_id = add_market.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let add_market_result = engine.add_market(add_market, redis_connection).await;
+
+                match add_market_result {
+                    Ok(market) => {
+                        let add_market_json = serde_json::json!({
+                            "status": "Added Market",
+                            "market": market,
+                        });
+
+                        let add_market_string = serde_json::to_string(&add_market_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, add_market_string)
+                            .await;
+                        println!("Successfully added market!")
+                    }
+                    Err(str) => {
+                        let add_market_json = serde_json::json!({
+                            "status": "Failed to Add Market",
+                            "reason": str,
+                        });
+
+                        let add_market_string = serde_json::to_string(&add_market_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, add_market_string)
+                            .await;
+                        println!("Adding market failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::UpdateMarketStatus(update_market_status) => {
+                println!("Update Market Status: {:?}", update_market_status);
+                let pubsub_id = update_market_status.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let update_market_status_result = engine
+                    .update_market_status(update_market_status, redis_connection)
+                    .await;
+
+                match update_market_status_result {
+                    Ok(market) => {
+                        let update_market_status_json = serde_json::json!({
+                            "status": "Updated Market Status",
+                            "market": market,
+                        });
+
+                        let update_market_status_string =
+                            serde_json::to_string(&update_market_status_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, update_market_status_string)
+                            .await;
+                        println!("Successfully updated market status!")
+                    }
+                    Err(str) => {
+                        let update_market_status_json = serde_json::json!({
+                            "status": "Failed to Update Market Status",
+                            "reason": str,
+                        });
+
+                        let update_market_status_string =
+                            serde_json::to_string(&update_market_status_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, update_market_status_string)
+                            .await;
+                        println!("Market status update failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::SetTradingState(set_trading_state) => {
+                println!("Set Trading State: {:?}", set_trading_state);
+                let pubsub_id = set_trading_state.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let set_trading_state_result = engine
+                    .set_trading_state(set_trading_state, redis_connection)
+                    .await;
+
+                match set_trading_state_result {
+                    Ok(market) => {
+                        let set_trading_state_json = serde_json::json!({
+                            "status": "Updated Trading State",
+                            "market": market,
+                        });
+
+                        let set_trading_state_string =
+                            serde_json::to_string(&set_trading_state_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_trading_state_string)
+                            .await;
+                        println!("Successfully updated trading state!")
+                    }
+                    Err(str) => {
+                        let set_trading_state_json = serde_json::json!({
+                            "status": "Failed to Update Trading State",
+                            "reason": str,
+                        });
+
+                        let set_trading_state_string =
+                            serde_json::to_string(&set_trading_state_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_trading_state_string)
+                            .await;
+                        println!("Trading state update failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::SetPriceProtection(set_price_protection) => {
+                println!("Set Price Protection: {:?}", set_price_protection);
+                let pubsub_id = set_price_protection.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let set_price_protection_result = engine
+                    .set_price_protection(set_price_protection, redis_connection)
+                    .await;
+
+                match set_price_protection_result {
+                    Ok(market) => {
+                        let set_price_protection_json = serde_json::json!({
+                            "status": "Updated Price Protection",
+                            "market": market,
+                        });
+
+                        let set_price_protection_string =
+                            serde_json::to_string(&set_price_protection_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_price_protection_string)
+                            .await;
+                        println!("Successfully updated price protection!")
+                    }
+                    Err(str) => {
+                        let set_price_protection_json = serde_json::json!({
+                            "status": "Failed to Update Price Protection",
+                            "reason": str,
+                        });
+
+                        let set_price_protection_string =
+                            serde_json::to_string(&set_price_protection_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_price_protection_string)
+                            .await;
+                        println!("Price protection update failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::SetMatchingPolicy(set_matching_policy) => {
+                println!("Set Matching Policy: {:?}", set_matching_policy);
+                let pubsub_id = set_matching_policy.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let set_matching_policy_result = engine
+                    .set_matching_policy(set_matching_policy, redis_connection)
+                    .await;
+
+                match set_matching_policy_result {
+                    Ok(market) => {
+                        let set_matching_policy_json = serde_json::json!({
+                            "status": "Updated Matching Policy",
+                            "market": market,
+                        });
+
+                        let set_matching_policy_string =
+                            serde_json::to_string(&set_matching_policy_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_matching_policy_string)
+                            .await;
+                        println!("Successfully updated matching policy!")
+                    }
+                    Err(str) => {
+                        let set_matching_policy_json = serde_json::json!({
+                            "status": "Failed to Update Matching Policy",
+                            "reason": str,
+                        });
+
+                        let set_matching_policy_string =
+                            serde_json::to_string(&set_matching_policy_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, set_matching_policy_string)
+                            .await;
+                        println!("Matching policy update failed - {}", str)
+                    }
+                }
+            }
+
+            OrderRequests::UpsertAsset(upsert_asset) => {
+                println!("Upsert Asset: {:?}", upsert_asset);
+                let pubsub_id = upsert_asset.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let upsert_asset_result = engine.upsert_asset(upsert_asset, redis_connection).await;
+
+                match upsert_asset_result {
+                    Ok(asset) => {
+                        let upsert_asset_json = serde_json::json!({
+                            "status": "Updated Asset",
+                            "asset": asset,
+                        });
+
+                        let upsert_asset_string =
+                            serde_json::to_string(&upsert_asset_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, upsert_asset_string)
+                            .await;
+                        println!("Successfully updated asset!")
+                    }
+                    Err(str) => {
+                        let upsert_asset_json = serde_json::json!({
+                            "status": "Failed to Update Asset",
+                            "reason": str,
+                        });
+
+                        let upsert_asset_string =
+                            serde_json::to_string(&upsert_asset_json).unwrap();
+
+                        let _ = redis_connection
+                            .publish(pubsub_id_ref, upsert_asset_string)
+                            .await;
+                        println!("Asset update failed - {}", str)
+                    }
+                }
+            }
+
             OrderRequests::GetOpenOrders(open_orders) => {
                 println!("Open Order: {:?}", open_orders);
                 let pubsub_id = open_orders.pubsub_id.unwrap().to_string();
@@ -155,13 +482,16 @@ pub async fn handle_order(
                 let pubsub_id = cancel_all_orders.pubsub_id.unwrap().to_string();
                 let pubsub_id_ref = pubsub_id.as_str();
 
-                let cancel_all_orders_result = engine.cancel_all_orders(cancel_all_orders);
+                let cancel_all_orders_result = engine
+                    .cancel_all_orders(cancel_all_orders, redis_connection)
+                    .await;
 
                 match cancel_all_orders_result {
-                    Ok(_) => {
+                    Ok(cancelled_orders) => {
                         let cancel_all_orders_json = serde_json::json!({
                             "status": "Cancelled All Orders",
                             "user_id": user_id,
+                            "cancelled_orders": cancelled_orders,
                         });
 
                         let cancel_all_orders_string =
@@ -175,6 +505,7 @@ pub async fn handle_order(
                     Err(str) => {
                         let cancel_all_orders_json = serde_json::json!({
                             "status": "Failed to Cancel All Orders",
+                            "reason": str,
                         });
 
                         let cancel_all_orders_string =
@@ -204,6 +535,39 @@ pub async fn handle_order(
                 let _ = redis_connection.publish(pubsub_id_ref, depth_string).await;
                 println!("Successfully retrieved depth!");
             }
+
+            OrderRequests::GetExchangeInfo(exchange_info) => {
+                println!("Get Exchange Info: {:?}", exchange_info);
+                let pubsub_id = exchange_info.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let exchange_info_json = serde_json::json!({
+                    "assets": engine.get_assets(),
+                    "symbols": engine.get_exchange_info(),
+                });
+
+                let exchange_info_string = serde_json::to_string(&exchange_info_json).unwrap();
+
+                let _ = redis_connection
+                    .publish(pubsub_id_ref, exchange_info_string)
+                    .await;
+                println!("Successfully retrieved exchange info!");
+            }
+
+            OrderRequests::GetFeeTier(fee_tier) => {
+                println!("Get Fee Tier: {:?}", fee_tier);
+                let pubsub_id = fee_tier.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let fee_tier_json = engine.get_fee_tier(fee_tier);
+
+                let fee_tier_string = serde_json::to_string(&fee_tier_json).unwrap();
+
+                let _ = redis_connection
+                    .publish(pubsub_id_ref, fee_tier_string)
+                    .await;
+                println!("Successfully retrieved fee tier!");
+            }
         },
         Err(err) => {
             println!("Failed to deserialize order request: {:?}", err);
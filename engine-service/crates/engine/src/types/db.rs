@@ -5,6 +5,9 @@ use serde::{Deserialize, Serialize};
 pub enum DatabaseRequests {
     InsertTrade(DbTrade),
     InsertOrder(DbOrder),
+    UpsertMarket(DbMarket),
+    UpsertAsset(DbAsset),
+    UpdateBalance(DbBalance),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +27,30 @@ pub struct DbTrade {
     pub base_asset: String,
     #[serde(default)]
     pub quote_asset: String,
+    // fees are charged in the asset each side received
+    #[serde(default)]
+    pub taker_fee: Decimal,
+    #[serde(default)]
+    pub taker_fee_asset: String,
+    #[serde(default)]
+    pub maker_fee: Decimal,
+    #[serde(default)]
+    pub maker_fee_asset: String,
+    // the balance movements the trade settled, written to the settlement outbox with it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settlement_legs: Vec<DbSettlementLeg>,
+}
+
+// one movement on one user's balance in the user service - legs of a trade or balance change are applied
+// in `seq` order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbSettlementLeg {
+    pub leg: String,
+    pub seq: i32,
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    pub operation: String, // add, subtract, lock or unlock
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,4 +65,82 @@ pub struct DbOrder {
     pub order_type: String,
     pub order_status: String,
     pub timestamp: i64,
+    #[serde(default = "default_time_in_force")]
+    pub time_in_force: String,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub trigger_price: Option<Decimal>,
+    #[serde(default)]
+    pub trailing_offset: Option<Decimal>,
+    #[serde(default)]
+    pub trailing_percent: Option<Decimal>,
+    #[serde(default)]
+    pub oco_group_id: Option<String>,
+    #[serde(default)]
+    pub display_quantity: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbMarket {
+    pub symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub min_order_size: Decimal,
+    pub max_order_size: Decimal,
+    pub price_precision: i32,
+    pub quantity_precision: i32,
+    pub status: String,
+    #[serde(default = "default_trading_state")]
+    pub trading_state: String,
+    #[serde(default)]
+    pub price_band_percent: Option<Decimal>,
+    #[serde(default)]
+    pub reference_price: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_percent: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default)]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(default = "default_matching_policy")]
+    pub matching_policy: String,
+    #[serde(default)]
+    pub maker_fee_rate: Decimal,
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbAsset {
+    pub symbol: String,
+    pub decimals: i32,
+    pub status: String,
+}
+
+// a user's balance of one asset after a ledger change. a change that did not settle a trade carries the
+// legs that replay it on the user service, written to the settlement outbox under its id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbBalance {
+    pub user_id: String,
+    pub asset: String,
+    pub available: Decimal,
+    pub locked: Decimal,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub change_id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settlement_legs: Vec<DbSettlementLeg>,
+}
+
+fn default_time_in_force() -> String {
+    "GTC".to_string()
+}
+
+fn default_trading_state() -> String {
+    "OPEN".to_string()
+}
+
+fn default_matching_policy() -> String {
+    "FIFO".to_string()
 }
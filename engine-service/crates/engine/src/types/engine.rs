@@ -2,39 +2,31 @@ use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+// an asset symbol such as SOL or MRBEAST - decimals and status live in the asset registry
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Hash)]
-pub enum Asset {
-    USDC,
-    USDT,
-    BTC,
-    ETH,
-    SOL,
-}
+#[serde(transparent)]
+pub struct Asset(pub String);
 
 impl std::fmt::Display for Asset {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Asset::USDC => write!(f, "USDC"),
-            Asset::USDT => write!(f, "USDT"),
-            Asset::BTC => write!(f, "BTC"),
-            Asset::ETH => write!(f, "ETH"),
-            Asset::SOL => write!(f, "SOL"),
-        }
+        write!(f, "{}", self.0)
     }
 }
 
-impl Asset {
-    pub fn from_str(asset_str: &str) -> Result<Asset, &'static str> {
-        // static lifetime because Err str slice is static
-        match asset_str {
-            "USDC" => Ok(Asset::USDC),
-            "USDT" => Ok(Asset::USDT),
-            "BTC" => Ok(Asset::BTC),
-            "ETH" => Ok(Asset::ETH),
-            "SOL" => Ok(Asset::SOL),
-            _ => Err("Unsupported asset"),
-        }
-    }
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub enum AssetStatus {
+    #[default]
+    ACTIVE,
+    DISABLED, // markets using the asset stop taking new orders
+}
+
+// one asset in the registry - seeded from assets.json, then managed through admin requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetConfig {
+    pub symbol: String,
+    pub decimals: u32,
+    #[serde(default)]
+    pub status: AssetStatus,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -43,7 +35,70 @@ pub struct AssetPair {
     pub quote: Asset,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub enum MarketStatus {
+    #[default]
+    TRADING,
+    PAUSED,   // cancels and reads only
+    DELISTED, // no orders left, kept so the market can be relisted
+}
+
+// what a market accepts right now - set by admins, e.g. to freeze a market during an incident
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub enum TradingState {
+    #[default]
+    OPEN,
+    #[allow(non_camel_case_types)]
+    POST_ONLY, // only limit orders that rest without crossing
+    #[allow(non_camel_case_types)]
+    CANCEL_ONLY, // cancels and reads
+    HALTED,  // reads only
+    AUCTION, // orders collect without matching until the book uncrosses at a single price
+}
+
+// how the resting orders of one price level share an incoming order
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub enum MatchingPolicy {
+    #[default]
+    FIFO, // price-time priority
+    #[allow(non_camel_case_types)]
+    PRO_RATA, // in proportion to each order's size
+    HYBRID, // the oldest order at the level fills first, then pro-rata
+}
+
+// one market in the registry - seeded from markets.json, then managed through admin requests
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketConfig {
+    pub symbol: String,
+    pub base_asset: Asset,
+    pub quote_asset: Asset,
+    pub min_order_size: Decimal,
+    pub max_order_size: Decimal,
+    pub price_precision: u32,
+    pub quantity_precision: u32,
+    #[serde(default)]
+    pub status: MarketStatus,
+    #[serde(default)]
+    pub trading_state: TradingState,
+    #[serde(default)]
+    pub price_band_percent: Option<Decimal>, // takers stop matching this far from the last trade
+    #[serde(default)]
+    pub reference_price: Option<Decimal>, // centre of the price band until the market first trades
+    #[serde(default)]
+    pub circuit_breaker_percent: Option<Decimal>, // halt once prices move more than this within the window
+    #[serde(default)]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default)]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(default)]
+    pub matching_policy: MatchingPolicy,
+    #[serde(default)]
+    pub maker_fee_rate: Decimal, // share of what the resting side receives that it pays as a fee
+    #[serde(default)]
+    pub taker_fee_rate: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum OrderSide {
     BUY,
     SELL,
@@ -53,6 +108,69 @@ pub enum OrderSide {
 pub enum OrderType {
     LIMIT,
     MARKET,
+    #[allow(non_camel_case_types)]
+    STOP_MARKET,
+    #[allow(non_camel_case_types)]
+    STOP_LIMIT,
+    #[allow(non_camel_case_types)]
+    TAKE_PROFIT_MARKET,
+    #[allow(non_camel_case_types)]
+    TAKE_PROFIT_LIMIT,
+    #[allow(non_camel_case_types)]
+    TRAILING_STOP_MARKET,
+    #[allow(non_camel_case_types)]
+    TRAILING_STOP_LIMIT,
+}
+
+impl OrderType {
+    // trigger orders wait in the trigger book until a trade crosses their trigger price
+    pub fn is_trigger(&self) -> bool {
+        !matches!(self, OrderType::LIMIT | OrderType::MARKET)
+    }
+
+    // the order type a trigger order is placed as once it fires
+    pub fn triggered_order_type(&self) -> OrderType {
+        match self {
+            OrderType::STOP_MARKET
+            | OrderType::TAKE_PROFIT_MARKET
+            | OrderType::TRAILING_STOP_MARKET => OrderType::MARKET,
+            OrderType::STOP_LIMIT
+            | OrderType::TAKE_PROFIT_LIMIT
+            | OrderType::TRAILING_STOP_LIMIT => OrderType::LIMIT,
+            order_type => order_type.clone(),
+        }
+    }
+
+    // trailing stops move their trigger price as the market moves in their favour
+    pub fn is_trailing(&self) -> bool {
+        matches!(
+            self,
+            OrderType::TRAILING_STOP_MARKET | OrderType::TRAILING_STOP_LIMIT
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    #[default]
+    GTC, // good till cancelled
+    IOC, // immediate or cancel - fill what you can, cancel the rest
+    FOK, // fill or kill - fill completely or not at all
+    GTD, // good till date - rests until expires_at
+}
+
+// what happens when an order would trade against a resting order of the same user
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    #[default]
+    #[allow(non_camel_case_types)]
+    CANCEL_NEWEST, // cancel the rest of the incoming order
+    #[allow(non_camel_case_types)]
+    CANCEL_OLDEST, // cancel the resting order and keep matching
+    #[allow(non_camel_case_types)]
+    CANCEL_BOTH,
+    #[allow(non_camel_case_types)]
+    DECREMENT_AND_CANCEL, // take the smaller quantity off both, cancelling whichever reaches zero
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +192,28 @@ pub struct Order {
     pub order_type: OrderType,
     pub order_status: OrderStatus,
     pub timestamp: i64, // chrono::Utc::now().timestamp_millis();
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>, // only set for GTD orders, in millis
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub post_only_reprice: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<Decimal>, // only set for stop and take profit orders, current level for trailing stops
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_offset: Option<Decimal>, // trailing stops trail the best trade price by a fixed amount
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_percent: Option<Decimal>, // or by a percentage of it, e.g. 1.5 for 1.5%
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oco_group_id: Option<String>, // shared by both legs of a one-cancels-other pair
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_quantity: Option<Decimal>, // iceberg orders only show this much of their quantity at a time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible_quantity: Option<Decimal>, // what is left of the iceberg's current visible slice
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention, // only used while the order is the taker
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,26 +223,91 @@ pub struct Fill {
     pub trade_id: i64,
     pub other_user_id: String,
     pub order_id: String,
+    #[serde(default)]
+    pub taker_fee: Decimal, // set when the fill is settled
+    #[serde(default)]
+    pub maker_fee: Decimal,
+}
+
+// one match made when an auction uncrosses - both orders as the match left them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionTrade {
+    pub buy_order: Order,
+    pub sell_order: Order,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub trade_id: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessOrderResult {
     pub executed_quantity: Decimal,
     pub fills: Vec<Fill>,
+    pub cancelled_quantity: Decimal, // unfilled remainder that was not rested on the book
+    pub cancelled_orders: Vec<(Order, Decimal)>, // resting orders hit by self-trade prevention, with the quantity taken off each - Cancelled once off the book
+    #[serde(skip)]
+    pub filled_orders: Vec<Order>, // resting orders the fills touched, as the last fill left them
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrder {
     pub market: String,
+    #[serde(default)] // market orders don't need a price
     pub price: Decimal,
+    #[serde(default)] // derived from the quote quantity when one is given
     pub quantity: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote_quantity: Option<Decimal>, // market buys only - spend this much quote asset instead
     pub side: OrderSide,
     pub order_type: OrderType,
     pub user_id: String,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub post_only: bool, // maker-only - never takes liquidity
+    #[serde(default)]
+    pub post_only_reprice: bool, // move a crossing post-only order one tick away instead of rejecting it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<Decimal>, // required for stop and take profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_offset: Option<Decimal>, // trailing stops need either an offset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_percent: Option<Decimal>, // or a percentage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_quantity: Option<Decimal>, // makes the order an iceberg that only shows this much at a time
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    #[serde(skip)] // only set by the engine for OCO legs
+    pub oco_group_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
 
+// a take profit limit order and a stop order for the same quantity - whichever fills or is cancelled first cancels the other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateOcoOrder {
+    pub market: String,
+    pub quantity: Decimal,
+    pub side: OrderSide,
+    pub user_id: String,
+    pub price: Decimal,         // limit leg
+    pub trigger_price: Decimal, // stop leg
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_limit_price: Option<Decimal>, // stop leg fires as a limit order at this price, or as a market order if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcoOrderResult {
+    pub oco_group_id: String,
+    pub limit_order_id: String,
+    pub stop_order_id: String,
+    pub executed_quantity: Decimal, // of the limit leg
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetOpenOrder {
     pub user_id: String,
@@ -116,13 +321,25 @@ pub struct GetOpenOrder {
 pub struct CancelOrder {
     pub order_id: String,
     pub user_id: String,
-    pub price: Decimal,
-    pub side: OrderSide,
     pub market: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
 
+// new price and/or quantity for a resting order - only a quantity reduction keeps its queue position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifyOrder {
+    pub order_id: String,
+    pub user_id: String,
+    pub market: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetOpenOrders {
     pub user_id: String,
@@ -131,14 +348,28 @@ pub struct GetOpenOrders {
     pub pubsub_id: Option<Uuid>,
 }
 
+// cancels the user's orders in `market` and `markets`, or in every market if neither is given
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelAllOrders {
     pub user_id: String,
-    pub market: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<String>,
+    #[serde(default)]
+    pub markets: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<OrderSide>, // only cancel this side
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelledOrder {
+    pub market: String,
+    pub order: Order,
+    pub released_asset: String,
+    pub released_amount: Decimal, // trigger orders have nothing locked, so nothing is released
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetDepth {
     pub symbol: String,
@@ -146,6 +377,76 @@ pub struct GetDepth {
     pub pubsub_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetExchangeInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetFeeTier {
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddMarket {
+    pub market: MarketConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetTradingState {
+    pub market: String,
+    pub trading_state: TradingState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetMatchingPolicy {
+    pub market: String,
+    pub matching_policy: MatchingPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+// replaces a market's price band and circuit breaker settings - unset fields turn them off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetPriceProtection {
+    pub market: String,
+    #[serde(default)]
+    pub price_band_percent: Option<Decimal>,
+    #[serde(default)]
+    pub reference_price: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_percent: Option<Decimal>,
+    #[serde(default)]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default)]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+// adds an asset, or changes the status of one already listed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertAsset {
+    pub asset: AssetConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateMarketStatus {
+    pub symbol: String,
+    pub status: MarketStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OrderRequests {
     CreateOrder(CreateOrder),
@@ -154,6 +455,16 @@ pub enum OrderRequests {
     GetOpenOrders(GetOpenOrders),
     GetDepth(GetDepth),
     CancelAllOrders(CancelAllOrders),
+    CreateOcoOrder(CreateOcoOrder),
+    ModifyOrder(ModifyOrder),
+    GetExchangeInfo(GetExchangeInfo),
+    AddMarket(AddMarket),
+    UpdateMarketStatus(UpdateMarketStatus),
+    UpsertAsset(UpsertAsset),
+    SetTradingState(SetTradingState),
+    SetPriceProtection(SetPriceProtection),
+    SetMatchingPolicy(SetMatchingPolicy),
+    GetFeeTier(GetFeeTier),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -163,7 +474,19 @@ pub struct CreateUserInput {
     pub pubsub_id: Option<Uuid>,
 }
 
+// funds credited to or taken out of a user's balance in the engine's ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateBalanceInput {
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UserRequests {
     CreateUser(CreateUserInput),
+    Deposit(UpdateBalanceInput),
+    Withdraw(UpdateBalanceInput),
 }
@@ -42,6 +42,86 @@ pub async fn handle_user(
 
                 println!("Successfully created user!")
             }
+            UserRequests::Deposit(deposit) => {
+                println!("Deposit: {:?}", deposit);
+                let pubsub_id = deposit.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let deposit_result = engine
+                    .deposit(
+                        &deposit.user_id,
+                        &deposit.asset,
+                        deposit.amount,
+                        redis_connection,
+                    )
+                    .await;
+
+                let deposit_json = match deposit_result {
+                    Ok(balance) => {
+                        println!("Successfully deposited funds!");
+                        serde_json::json!({
+                            "status": "Deposited",
+                            "user_id": deposit.user_id,
+                            "asset": deposit.asset,
+                            "available": balance.available,
+                            "locked": balance.locked,
+                        })
+                    }
+                    Err(str) => {
+                        println!("Deposit failed - {}", str);
+                        serde_json::json!({
+                            "status": "Failed to Deposit",
+                            "reason": str,
+                        })
+                    }
+                };
+
+                let deposit_string = serde_json::to_string(&deposit_json).unwrap();
+
+                let _ = redis_connection
+                    .publish(pubsub_id_ref, deposit_string)
+                    .await;
+            }
+            UserRequests::Withdraw(withdraw) => {
+                println!("Withdraw: {:?}", withdraw);
+                let pubsub_id = withdraw.pubsub_id.unwrap().to_string();
+                let pubsub_id_ref = pubsub_id.as_str();
+
+                let withdraw_result = engine
+                    .withdraw(
+                        &withdraw.user_id,
+                        &withdraw.asset,
+                        withdraw.amount,
+                        redis_connection,
+                    )
+                    .await;
+
+                let withdraw_json = match withdraw_result {
+                    Ok(balance) => {
+                        println!("Successfully withdrew funds!");
+                        serde_json::json!({
+                            "status": "Withdrawn",
+                            "user_id": withdraw.user_id,
+                            "asset": withdraw.asset,
+                            "available": balance.available,
+                            "locked": balance.locked,
+                        })
+                    }
+                    Err(str) => {
+                        println!("Withdrawal failed - {}", str);
+                        serde_json::json!({
+                            "status": "Failed to Withdraw",
+                            "reason": str,
+                        })
+                    }
+                };
+
+                let withdraw_string = serde_json::to_string(&withdraw_json).unwrap();
+
+                let _ = redis_connection
+                    .publish(pubsub_id_ref, withdraw_string)
+                    .await;
+            }
         },
         Err(err) => {
             println!("Failed to deserialize user request: {:?}", err);
@@ -1,7 +1,8 @@
 use std::fmt::Error;
+use std::time::Duration;
 use uuid::Uuid;
 
-use fred::types::RedisConfig;
+use fred::types::{PerformanceConfig, RedisConfig};
 use fred::{clients::SubscriberClient, prelude::*};
 
 pub enum RedisQueues {
@@ -51,6 +52,27 @@ impl RedisManager {
         })
     }
 
+    // a manager that never connects - every command times out after a millisecond instead of
+    // waiting for a server, so code that only logs redis errors can run without one, e.g. in tests
+    pub fn disconnected() -> Result<Self, RedisError> {
+        let config = RedisConfig::default();
+        let timeout = |perf: &mut PerformanceConfig| {
+            perf.default_command_timeout = Duration::from_millis(1);
+        };
+
+        Ok(Self {
+            client: Builder::from_config(config.clone())
+                .with_performance_config(timeout)
+                .build()?,
+            publisher: Builder::from_config(config.clone())
+                .with_performance_config(timeout)
+                .build()?,
+            subscriber: Builder::from_config(config)
+                .with_performance_config(timeout)
+                .build_subscriber_client()?,
+        })
+    }
+
     pub async fn push(&self, key: &str, value: String) -> Result<(), RedisError> {
         self.client.lpush(key, value).await
     }
@@ -63,6 +85,14 @@ impl RedisManager {
         self.client.rpop(key, count).await
     }
 
+    pub async fn set(&self, key: &str, value: String) -> Result<(), RedisError> {
+        self.client.set(key, value, None, None, false).await
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<String>, RedisError> {
+        self.client.get(key).await
+    }
+
     pub async fn publish(&self, channel: &str, value: String) -> Result<(), RedisError> {
         self.publisher.publish(channel, value).await
     }
@@ -5,7 +5,7 @@ use actix_web::{
 };
 use confik::{Configuration as _, EnvSource};
 use dotenvy::dotenv;
-use routes::{depth, klines, order, tickers, trade, user};
+use routes::{admin, depth, exchange_info, fees, klines, order, tickers, trade, user};
 use sqlx_postgres::PostgresDb;
 
 pub mod config;
@@ -51,12 +51,35 @@ async fn main() -> std::io::Result<()> {
                     .service(web::scope("/depth").route("", web::get().to(depth::get_depth))) // GET /depth?symbol=SOL_USDC
                     .service(web::scope("/trades").route("", web::get().to(trade::get_trades))) // GET /trades?symbol=SOL_USDC
                     .service(web::scope("/klines").route("", web::get().to(klines::get_klines))) // GET /klines?symbol=SOL_USDC&interval=1m&startTime=1727022600
+                    .service(web::scope("/exchangeInfo").route("", web::get().to(exchange_info::get_exchange_info))) // GET /exchangeInfo
+                    .service(web::scope("/fees").route("", web::get().to(fees::get_fee_tier))) // GET /fees?user_id=1
                     .service(web::scope("/tickers").route("", web::get().to(tickers::get_tickers))) // GET /klines?symbol=SOL_USDC&interval=1m&startTime=1727022600
                     .service(
                         web::scope("/order")
                             .route("", web::get().to(order::get_open_order)) // GET /order
                             .route("", web::post().to(order::execute_order)) // POST /order
-                            .route("", web::delete().to(order::cancel_order)), // DELETE /order
+                            .route("", web::delete().to(order::cancel_order)) // DELETE /order
+                            .route("", web::patch().to(order::modify_order)) // PATCH /order
+                            .route("/oco", web::post().to(order::execute_oco_order)), // POST /order/oco
+                    )
+                    .service(
+                        web::scope("/admin/markets")
+                            .route("", web::post().to(admin::add_market)) // POST /admin/markets
+                            .route("", web::patch().to(admin::update_market_status)) // PATCH /admin/markets
+                            .route("/state", web::patch().to(admin::set_trading_state)) // PATCH /admin/markets/state
+                            .route("/protection", web::patch().to(admin::set_price_protection)) // PATCH /admin/markets/protection
+                            .route("/matching", web::patch().to(admin::set_matching_policy)), // PATCH /admin/markets/matching
+                    )
+                    .service(web::scope("/admin/assets").route("", web::put().to(admin::upsert_asset))) // PUT /admin/assets
+                    .service(
+                        web::scope("/admin/balances")
+                            .route("/deposit", web::post().to(admin::deposit)) // POST /admin/balances/deposit
+                            .route("/withdraw", web::post().to(admin::withdraw)), // POST /admin/balances/withdraw
+                    )
+                    .service(
+                        web::scope("/admin/settlements")
+                            .route("", web::get().to(admin::get_settlements)) // GET /admin/settlements?status=FAILED
+                            .route("/replay", web::post().to(admin::replay_settlement)), // POST /admin/settlements/replay
                     )
                     .service(
                         web::scope("/orders")
@@ -0,0 +1,483 @@
+use actix_web::{
+    web::{Data, Json, Query},
+    HttpRequest,
+};
+
+use db_processor::query::{get_settlement_legs_from_db, replay_settlement_legs};
+use serde_json::to_string;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::types::{
+    app::AppState,
+    routes::{
+        AddMarketInput, GetSettlementsInput, OrderRequests, ReplaySettlementInput,
+        SetMatchingPolicyInput, SetPriceProtectionInput, SetTradingStateInput, UpdateBalanceInput,
+        UpdateMarketStatusInput, UpsertAssetInput, UserRequests,
+    },
+};
+
+use redis::RedisQueues;
+
+// admin requests must carry the key from ADMIN_API_KEY in the x-admin-key header
+fn is_admin(req: &HttpRequest) -> bool {
+    let admin_key = match std::env::var("ADMIN_API_KEY") {
+        Ok(admin_key) if !admin_key.is_empty() => admin_key,
+        _ => return false,
+    };
+
+    req.headers()
+        .get("x-admin-key")
+        .and_then(|header| header.to_str().ok())
+        .is_some_and(|header| header == admin_key)
+}
+
+pub async fn add_market(
+    req: HttpRequest,
+    body: Json<AddMarketInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut market = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    market.pubsub_id = pubsub_id;
+
+    let add_market_request = OrderRequests::AddMarket(market);
+    let add_market_data = to_string(&add_market_request).unwrap();
+    println!("Add Market: {}", add_market_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                add_market_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get added market from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+pub async fn update_market_status(
+    req: HttpRequest,
+    body: Json<UpdateMarketStatusInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut market_status = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    market_status.pubsub_id = pubsub_id;
+
+    let update_market_status_request = OrderRequests::UpdateMarketStatus(market_status);
+    let update_market_status_data = to_string(&update_market_status_request).unwrap();
+    println!("Update Market Status: {}", update_market_status_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                update_market_status_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get market status update from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+pub async fn upsert_asset(
+    req: HttpRequest,
+    body: Json<UpsertAssetInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut asset = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    asset.pubsub_id = pubsub_id;
+
+    let upsert_asset_request = OrderRequests::UpsertAsset(asset);
+    let upsert_asset_data = to_string(&upsert_asset_request).unwrap();
+    println!("Upsert Asset: {}", upsert_asset_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                upsert_asset_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get asset update from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+pub async fn set_trading_state(
+    req: HttpRequest,
+    body: Json<SetTradingStateInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut trading_state = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    trading_state.pubsub_id = pubsub_id;
+
+    let set_trading_state_request = OrderRequests::SetTradingState(trading_state);
+    let set_trading_state_data = to_string(&set_trading_state_request).unwrap();
+    println!("Set Trading State: {}", set_trading_state_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                set_trading_state_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get trading state update from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+pub async fn set_price_protection(
+    req: HttpRequest,
+    body: Json<SetPriceProtectionInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut price_protection = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    price_protection.pubsub_id = pubsub_id;
+
+    let set_price_protection_request = OrderRequests::SetPriceProtection(price_protection);
+    let set_price_protection_data = to_string(&set_price_protection_request).unwrap();
+    println!("Set Price Protection: {}", set_price_protection_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                set_price_protection_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get price protection update from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+pub async fn set_matching_policy(
+    req: HttpRequest,
+    body: Json<SetMatchingPolicyInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut matching_policy = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    matching_policy.pubsub_id = pubsub_id;
+
+    let set_matching_policy_request = OrderRequests::SetMatchingPolicy(matching_policy);
+    let set_matching_policy_data = to_string(&set_matching_policy_request).unwrap();
+    println!("Set Matching Policy: {}", set_matching_policy_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                set_matching_policy_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get matching policy update from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+// balances live in the engine's ledger, so confirmed deposits are credited through it
+pub async fn deposit(
+    req: HttpRequest,
+    body: Json<UpdateBalanceInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut deposit = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    deposit.pubsub_id = pubsub_id;
+
+    let deposit_request = UserRequests::Deposit(deposit);
+    let deposit_data = to_string(&deposit_request).unwrap();
+    println!("Deposit: {}", deposit_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::USERS.to_string(),
+                deposit_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get deposit from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+// withdrawals are taken out of the ledger before the funds leave the exchange
+pub async fn withdraw(
+    req: HttpRequest,
+    body: Json<UpdateBalanceInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let mut withdraw = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    withdraw.pubsub_id = pubsub_id;
+
+    let withdraw_request = UserRequests::Withdraw(withdraw);
+    let withdraw_data = to_string(&withdraw_request).unwrap();
+    println!("Withdraw: {}", withdraw_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::USERS.to_string(),
+                withdraw_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get withdrawal from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
+// settlement legs of the outbox in a given status - the failed ones by default
+pub async fn get_settlements(
+    req: HttpRequest,
+    query: Query<GetSettlementsInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let settlements = query.into_inner();
+    println!("Get Settlements: {}", settlements.status);
+
+    let pg_pool = app_state.postgres_db.get_pg_connection().unwrap();
+
+    match get_settlement_legs_from_db(&pg_pool, settlements.status).await {
+        Ok(legs) => {
+            println!("Time: {:?}", starttime.elapsed());
+            actix_web::HttpResponse::Ok().json(legs)
+        }
+        Err(e) => {
+            println!("Failed to get settlements from db - {}", e);
+            println!("Time: {:?}", starttime.elapsed());
+            actix_web::HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+// failed legs are retried from scratch - the idempotency key keeps the user service from applying one twice
+pub async fn replay_settlement(
+    req: HttpRequest,
+    body: Json<ReplaySettlementInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    if !is_admin(&req) {
+        return actix_web::HttpResponse::Unauthorized().finish();
+    }
+
+    let starttime = Instant::now();
+    let replay = body.into_inner();
+    println!(
+        "Replay Settlement: {} {:?}",
+        replay.settlement_id, replay.leg
+    );
+
+    let pg_pool = app_state.postgres_db.get_pg_connection().unwrap();
+
+    match replay_settlement_legs(&pg_pool, replay.settlement_id, replay.leg).await {
+        Ok(0) => actix_web::HttpResponse::NotFound().json(serde_json::json!({
+            "error": "No failed legs for this settlement"
+        })),
+        Ok(replayed) => {
+            println!("Time: {:?}", starttime.elapsed());
+            actix_web::HttpResponse::Ok().json(serde_json::json!({ "replayed": replayed }))
+        }
+        Err(e) => {
+            println!("Failed to replay settlement - {}", e);
+            println!("Time: {:?}", starttime.elapsed());
+            actix_web::HttpResponse::InternalServerError().finish()
+        }
+    }
+}
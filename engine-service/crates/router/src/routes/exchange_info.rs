@@ -0,0 +1,51 @@
+use actix_web::web::Data;
+
+use serde_json::to_string;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::types::{
+    app::AppState,
+    routes::{GetExchangeInfoInput, OrderRequests},
+};
+
+use redis::RedisQueues;
+
+pub async fn get_exchange_info(app_state: Data<AppState>) -> actix_web::HttpResponse {
+    let starttime = Instant::now();
+    let pubsub_id = Some(Uuid::new_v4());
+
+    let get_exchange_info_request =
+        OrderRequests::GetExchangeInfo(GetExchangeInfoInput { pubsub_id });
+    let get_exchange_info_data = to_string(&get_exchange_info_request).unwrap();
+    println!("Get Exchange Info: {}", get_exchange_info_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                get_exchange_info_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get exchange info from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
@@ -3,4 +3,7 @@ pub mod user;
 pub mod depth;
 pub mod trade;
 pub mod klines;
-pub mod tickers;
\ No newline at end of file
+pub mod tickers;
+pub mod exchange_info;
+pub mod admin;
+pub mod fees;
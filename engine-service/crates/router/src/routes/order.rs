@@ -8,7 +8,7 @@ use uuid::Uuid;
 use crate::types::{
     app::AppState,
     routes::{
-        CancelAllOrdersInput, CancelOrderInput, CreateOrderInput, GetOpenOrderInput, GetOpenOrdersInput, OrderRequests
+        CancelAllOrdersInput, CancelOrderInput, CreateOcoOrderInput, CreateOrderInput, GetOpenOrderInput, GetOpenOrdersInput, ModifyOrderInput, OrderRequests
     },
 };
 
@@ -58,6 +58,50 @@ pub async fn execute_order(
     actix_web::HttpResponse::Ok().finish()
 }
 
+pub async fn execute_oco_order(
+    body: Json<CreateOcoOrderInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    let starttime = Instant::now();
+    let mut order = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    order.pubsub_id = pubsub_id;
+
+    let create_oco_order_request = OrderRequests::CreateOcoOrder(order);
+    let create_oco_order_data = to_string(&create_oco_order_request).unwrap();
+    println!("Create OCO Order: {}", create_oco_order_data);
+
+    let redis_connection = &app_state.redis_connection;
+
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                create_oco_order_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get created OCO order from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
 pub async fn get_open_order(
     body: Json<GetOpenOrderInput>,
     app_state: Data<AppState>,
@@ -145,6 +189,49 @@ pub async fn cancel_order(
     actix_web::HttpResponse::Ok().finish()
 }
 
+pub async fn modify_order(
+    body: Json<ModifyOrderInput>,
+    app_state: Data<AppState>,
+) -> actix_web::HttpResponse {
+    let starttime = Instant::now();
+    let mut order = body.into_inner();
+    let pubsub_id = Some(Uuid::new_v4());
+    order.pubsub_id = pubsub_id;
+
+    let modify_order_request = OrderRequests::ModifyOrder(order);
+    let modify_order_data = to_string(&modify_order_request).unwrap();
+    println!("Modify Order: {}", modify_order_data);
+
+    let redis_connection = &app_state.redis_connection;
+    if let Some(pubsub_id_value) = pubsub_id {
+        let result = redis_connection
+            .push_and_wait_for_subscriber(
+                RedisQueues::ORDERS.to_string(),
+                modify_order_data,
+                pubsub_id_value,
+            )
+            .await;
+
+        match result {
+            Ok(published_data) => {
+                let published_data_json: serde_json::Value =
+                    serde_json::from_str(&published_data).unwrap();
+
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::Ok().json(published_data_json);
+            }
+            Err(e) => {
+                println!("Failed to get modified order from redis - {}", e);
+                println!("Time: {:?}", starttime.elapsed());
+                return actix_web::HttpResponse::InternalServerError().finish();
+            }
+        }
+    }
+
+    println!("Timeout: {:?}", starttime.elapsed());
+    actix_web::HttpResponse::Ok().finish()
+}
+
 pub async fn get_open_orders(
     body: Json<GetOpenOrdersInput>,
     app_state: Data<AppState>,
@@ -12,16 +12,86 @@ pub enum OrderSide {
 pub enum OrderType {
     LIMIT,
     MARKET,
+    #[allow(non_camel_case_types)]
+    STOP_MARKET,
+    #[allow(non_camel_case_types)]
+    STOP_LIMIT,
+    #[allow(non_camel_case_types)]
+    TAKE_PROFIT_MARKET,
+    #[allow(non_camel_case_types)]
+    TAKE_PROFIT_LIMIT,
+    #[allow(non_camel_case_types)]
+    TRAILING_STOP_MARKET,
+    #[allow(non_camel_case_types)]
+    TRAILING_STOP_LIMIT,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum TimeInForce {
+    #[default]
+    GTC,
+    IOC,
+    FOK,
+    GTD,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum SelfTradePrevention {
+    #[default]
+    #[allow(non_camel_case_types)]
+    CANCEL_NEWEST,
+    #[allow(non_camel_case_types)]
+    CANCEL_OLDEST,
+    #[allow(non_camel_case_types)]
+    CANCEL_BOTH,
+    #[allow(non_camel_case_types)]
+    DECREMENT_AND_CANCEL,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrderInput {
     pub market: String,
+    #[serde(default)] // market orders don't need a price
     pub price: Decimal,
+    #[serde(default)] // market buys for a quote amount don't need a quantity
     pub quantity: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote_quantity: Option<Decimal>, // market buys only - spend this much quote asset, e.g. 100 USDC of SOL
     pub side: OrderSide,
     pub order_type: OrderType,
     pub user_id: String,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>, // required for GTD orders, in millis
+    #[serde(default)]
+    pub post_only: bool,
+    #[serde(default)]
+    pub post_only_reprice: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<Decimal>, // required for stop and take profit orders
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_offset: Option<Decimal>, // trailing stops need either an offset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trailing_percent: Option<Decimal>, // or a percentage, e.g. 1.5 for 1.5%
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_quantity: Option<Decimal>, // iceberg orders only show this much of their quantity on the book
+    #[serde(default)]
+    pub self_trade_prevention: SelfTradePrevention,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateOcoOrderInput {
+    pub market: String,
+    pub quantity: Decimal,
+    pub side: OrderSide,
+    pub user_id: String,
+    pub price: Decimal,         // take profit limit leg
+    pub trigger_price: Decimal, // stop leg
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_limit_price: Option<Decimal>, // stop leg fires as a limit order at this price, or as a market order if unset
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
@@ -39,13 +109,24 @@ pub struct GetOpenOrderInput {
 pub struct CancelOrderInput {
     pub order_id: String,
     pub user_id: String,
-    pub price: Decimal,
-    pub side: OrderSide,
     pub market: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifyOrderInput {
+    pub order_id: String,
+    pub user_id: String,
+    pub market: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Decimal>, // new price, re-queues the order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantity: Option<Decimal>, // new total quantity, keeps queue position only if reduced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetOpenOrdersInput {
     pub user_id: String,
@@ -57,7 +138,12 @@ pub struct GetOpenOrdersInput {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelAllOrdersInput {
     user_id: String,
-    market: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    market: Option<String>,
+    #[serde(default)]
+    markets: Vec<String>, // with no market or markets, orders are cancelled in every market
+    #[serde(skip_serializing_if = "Option::is_none")]
+    side: Option<OrderSide>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pubsub_id: Option<Uuid>,
 }
@@ -69,6 +155,160 @@ pub struct GetDepthInput {
     pub pubsub_id: Option<Uuid>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetExchangeInfoInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetFeeTierInput {
+    pub user_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MarketStatus {
+    TRADING,
+    PAUSED,
+    DELISTED,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketInput {
+    pub symbol: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub min_order_size: Decimal,
+    pub max_order_size: Decimal,
+    pub price_precision: u32,
+    pub quantity_precision: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_band_percent: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_price: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_percent: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matching_policy: Option<MatchingPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maker_fee_rate: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub taker_fee_rate: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddMarketInput {
+    pub market: MarketInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TradingState {
+    OPEN,
+    #[allow(non_camel_case_types)]
+    POST_ONLY,
+    #[allow(non_camel_case_types)]
+    CANCEL_ONLY,
+    HALTED,
+    AUCTION,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetTradingStateInput {
+    pub market: String,
+    pub trading_state: TradingState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MatchingPolicy {
+    FIFO,
+    #[allow(non_camel_case_types)]
+    PRO_RATA,
+    HYBRID,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetMatchingPolicyInput {
+    pub market: String,
+    pub matching_policy: MatchingPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+// unset fields turn the price band or circuit breaker off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetPriceProtectionInput {
+    pub market: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_band_percent: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_price: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_percent: Option<Decimal>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_window_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker_halt_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AssetStatus {
+    ACTIVE,
+    DISABLED,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetInput {
+    pub symbol: String,
+    pub decimals: u32,
+    pub status: AssetStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpsertAssetInput {
+    pub asset: AssetInput,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateMarketStatusInput {
+    pub symbol: String,
+    pub status: MarketStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GetSettlementsInput {
+    #[serde(default = "failed_settlement_status")]
+    pub status: String,
+}
+
+fn failed_settlement_status() -> String {
+    "FAILED".to_string()
+}
+
+// puts a settlement's failed legs - or just the named one - back in the settlement outbox. the id is
+// `market:trade_id` for a trade and `balance:change_id` for any other balance change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaySettlementInput {
+    pub settlement_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leg: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetTradesInput {
     pub symbol: String,
@@ -91,6 +331,16 @@ pub enum OrderRequests {
     GetOpenOrders(GetOpenOrdersInput),
     CancelAllOrders(CancelAllOrdersInput),
     GetDepth(GetDepthInput),
+    CreateOcoOrder(CreateOcoOrderInput),
+    ModifyOrder(ModifyOrderInput),
+    GetExchangeInfo(GetExchangeInfoInput),
+    AddMarket(AddMarketInput),
+    UpdateMarketStatus(UpdateMarketStatusInput),
+    UpsertAsset(UpsertAssetInput),
+    SetTradingState(SetTradingStateInput),
+    SetPriceProtection(SetPriceProtectionInput),
+    SetMatchingPolicy(SetMatchingPolicyInput),
+    GetFeeTier(GetFeeTierInput),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,7 +350,19 @@ pub struct CreateUserInput {
     pub pubsub_id: Option<Uuid>,
 }
 
+// funds credited to or taken out of a user's balance in the engine's ledger
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateBalanceInput {
+    pub user_id: String,
+    pub asset: String,
+    pub amount: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pubsub_id: Option<Uuid>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UserRequests {
     CreateUser(CreateUserInput),
+    Deposit(UpdateBalanceInput),
+    Withdraw(UpdateBalanceInput),
 }
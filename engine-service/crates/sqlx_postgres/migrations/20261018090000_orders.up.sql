@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS orders (
+    order_id VARCHAR PRIMARY KEY,
+    market VARCHAR NOT NULL,
+    price NUMERIC NOT NULL,
+    quantity NUMERIC NOT NULL,
+    filled_quantity NUMERIC NOT NULL,
+    user_id VARCHAR NOT NULL,
+    side VARCHAR NOT NULL,
+    order_type VARCHAR NOT NULL,
+    order_status VARCHAR NOT NULL,
+    timestamp BIGINT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE orders
+    DROP COLUMN IF EXISTS time_in_force,
+    DROP COLUMN IF EXISTS expires_at;
\ No newline at end of file
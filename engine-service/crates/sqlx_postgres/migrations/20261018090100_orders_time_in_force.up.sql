@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS time_in_force VARCHAR NOT NULL DEFAULT 'GTC',
+    ADD COLUMN IF NOT EXISTS expires_at BIGINT;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE orders
+    DROP COLUMN IF EXISTS trailing_offset,
+    DROP COLUMN IF EXISTS trailing_percent;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS trailing_offset NUMERIC,
+    ADD COLUMN IF NOT EXISTS trailing_percent NUMERIC;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE orders
+    DROP COLUMN IF EXISTS oco_group_id;
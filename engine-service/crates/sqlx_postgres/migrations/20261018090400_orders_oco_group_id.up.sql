@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS oco_group_id VARCHAR;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS markets (
+    symbol VARCHAR PRIMARY KEY,
+    base_asset VARCHAR NOT NULL,
+    quote_asset VARCHAR NOT NULL,
+    min_order_size NUMERIC NOT NULL,
+    max_order_size NUMERIC NOT NULL,
+    price_precision INTEGER NOT NULL,
+    quantity_precision INTEGER NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'TRADING'
+);
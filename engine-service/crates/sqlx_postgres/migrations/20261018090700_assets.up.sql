@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS assets (
+    symbol VARCHAR PRIMARY KEY,
+    decimals INTEGER NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'ACTIVE'
+);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE markets
+    ADD COLUMN IF NOT EXISTS trading_state VARCHAR NOT NULL DEFAULT 'OPEN';
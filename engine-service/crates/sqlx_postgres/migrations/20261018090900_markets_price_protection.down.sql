@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE markets
+    DROP COLUMN IF EXISTS price_band_percent,
+    DROP COLUMN IF EXISTS reference_price,
+    DROP COLUMN IF EXISTS circuit_breaker_percent,
+    DROP COLUMN IF EXISTS circuit_breaker_window_secs,
+    DROP COLUMN IF EXISTS circuit_breaker_halt_secs;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+ALTER TABLE markets
+    ADD COLUMN IF NOT EXISTS price_band_percent NUMERIC,
+    ADD COLUMN IF NOT EXISTS reference_price NUMERIC,
+    ADD COLUMN IF NOT EXISTS circuit_breaker_percent NUMERIC,
+    ADD COLUMN IF NOT EXISTS circuit_breaker_window_secs BIGINT,
+    ADD COLUMN IF NOT EXISTS circuit_breaker_halt_secs BIGINT;
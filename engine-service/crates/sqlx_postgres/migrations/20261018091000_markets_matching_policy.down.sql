@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE markets
+    DROP COLUMN IF EXISTS matching_policy;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE markets
+    ADD COLUMN IF NOT EXISTS matching_policy VARCHAR NOT NULL DEFAULT 'FIFO';
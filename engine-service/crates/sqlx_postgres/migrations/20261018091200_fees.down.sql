@@ -0,0 +1,10 @@
+-- Add down migration script here
+ALTER TABLE trades
+    DROP COLUMN IF EXISTS taker_fee,
+    DROP COLUMN IF EXISTS taker_fee_asset,
+    DROP COLUMN IF EXISTS maker_fee,
+    DROP COLUMN IF EXISTS maker_fee_asset;
+
+ALTER TABLE markets
+    DROP COLUMN IF EXISTS maker_fee_rate,
+    DROP COLUMN IF EXISTS taker_fee_rate;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE markets
+    ADD COLUMN IF NOT EXISTS maker_fee_rate NUMERIC NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS taker_fee_rate NUMERIC NOT NULL DEFAULT 0;
+
+ALTER TABLE trades
+    ADD COLUMN IF NOT EXISTS taker_fee NUMERIC NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS taker_fee_asset VARCHAR NOT NULL DEFAULT '',
+    ADD COLUMN IF NOT EXISTS maker_fee NUMERIC NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS maker_fee_asset VARCHAR NOT NULL DEFAULT '';
@@ -0,0 +1,20 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS settlement_outbox (
+    id BIGSERIAL,
+    settlement_id VARCHAR NOT NULL,
+    leg VARCHAR NOT NULL,
+    seq INTEGER NOT NULL,
+    user_id VARCHAR NOT NULL,
+    asset VARCHAR NOT NULL,
+    amount NUMERIC NOT NULL,
+    operation VARCHAR NOT NULL,
+    status VARCHAR NOT NULL DEFAULT 'PENDING',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at BIGINT NOT NULL,
+    last_error VARCHAR,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (settlement_id, leg)
+);
+
+CREATE INDEX IF NOT EXISTS settlement_outbox_status_idx ON settlement_outbox (status, next_attempt_at);
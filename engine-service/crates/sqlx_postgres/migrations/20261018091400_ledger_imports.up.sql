@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS ledger_imports (
+    source VARCHAR PRIMARY KEY,
+    balances INTEGER NOT NULL,
+    imported_at BIGINT NOT NULL
+);
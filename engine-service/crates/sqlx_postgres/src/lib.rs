@@ -33,6 +33,213 @@ impl PostgresDb {
         .execute(&pool)
         .await?;
 
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS orders (
+                order_id VARCHAR PRIMARY KEY,
+                market VARCHAR NOT NULL,
+                price NUMERIC NOT NULL,
+                quantity NUMERIC NOT NULL,
+                filled_quantity NUMERIC NOT NULL,
+                user_id VARCHAR NOT NULL,
+                side VARCHAR NOT NULL,
+                order_type VARCHAR NOT NULL,
+                order_status VARCHAR NOT NULL,
+                timestamp BIGINT NOT NULL
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE orders
+                ADD COLUMN IF NOT EXISTS time_in_force VARCHAR NOT NULL DEFAULT 'GTC',
+                ADD COLUMN IF NOT EXISTS expires_at BIGINT;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE orders
+                ADD COLUMN IF NOT EXISTS trigger_price NUMERIC;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE orders
+                ADD COLUMN IF NOT EXISTS trailing_offset NUMERIC,
+                ADD COLUMN IF NOT EXISTS trailing_percent NUMERIC;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE orders
+                ADD COLUMN IF NOT EXISTS oco_group_id VARCHAR;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE orders
+                ADD COLUMN IF NOT EXISTS display_quantity NUMERIC;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS markets (
+                symbol VARCHAR PRIMARY KEY,
+                base_asset VARCHAR NOT NULL,
+                quote_asset VARCHAR NOT NULL,
+                min_order_size NUMERIC NOT NULL,
+                max_order_size NUMERIC NOT NULL,
+                price_precision INTEGER NOT NULL,
+                quantity_precision INTEGER NOT NULL,
+                status VARCHAR NOT NULL DEFAULT 'TRADING'
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE markets
+                ADD COLUMN IF NOT EXISTS trading_state VARCHAR NOT NULL DEFAULT 'OPEN';
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE markets
+                ADD COLUMN IF NOT EXISTS price_band_percent NUMERIC,
+                ADD COLUMN IF NOT EXISTS reference_price NUMERIC,
+                ADD COLUMN IF NOT EXISTS circuit_breaker_percent NUMERIC,
+                ADD COLUMN IF NOT EXISTS circuit_breaker_window_secs BIGINT,
+                ADD COLUMN IF NOT EXISTS circuit_breaker_halt_secs BIGINT;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE markets
+                ADD COLUMN IF NOT EXISTS matching_policy VARCHAR NOT NULL DEFAULT 'FIFO';
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE markets
+                ADD COLUMN IF NOT EXISTS maker_fee_rate NUMERIC NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS taker_fee_rate NUMERIC NOT NULL DEFAULT 0;
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            ALTER TABLE trades
+                ADD COLUMN IF NOT EXISTS taker_fee NUMERIC NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS taker_fee_asset VARCHAR NOT NULL DEFAULT '',
+                ADD COLUMN IF NOT EXISTS maker_fee NUMERIC NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS maker_fee_asset VARCHAR NOT NULL DEFAULT '';
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS assets (
+                symbol VARCHAR PRIMARY KEY,
+                decimals INTEGER NOT NULL,
+                status VARCHAR NOT NULL DEFAULT 'ACTIVE'
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS balances (
+                user_id VARCHAR NOT NULL,
+                asset VARCHAR NOT NULL,
+                available NUMERIC NOT NULL DEFAULT 0,
+                locked NUMERIC NOT NULL DEFAULT 0,
+                updated_at BIGINT NOT NULL,
+                PRIMARY KEY (user_id, asset)
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS settlement_outbox (
+                id BIGSERIAL,
+                settlement_id VARCHAR NOT NULL,
+                leg VARCHAR NOT NULL,
+                seq INTEGER NOT NULL,
+                user_id VARCHAR NOT NULL,
+                asset VARCHAR NOT NULL,
+                amount NUMERIC NOT NULL,
+                operation VARCHAR NOT NULL,
+                status VARCHAR NOT NULL DEFAULT 'PENDING',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                next_attempt_at BIGINT NOT NULL,
+                last_error VARCHAR,
+                created_at BIGINT NOT NULL,
+                updated_at BIGINT NOT NULL,
+                PRIMARY KEY (settlement_id, leg)
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS settlement_outbox_status_idx ON settlement_outbox (status, next_attempt_at);
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ledger_imports (
+                source VARCHAR PRIMARY KEY,
+                balances INTEGER NOT NULL,
+                imported_at BIGINT NOT NULL
+            );
+            "#
+        )
+        .execute(&pool)
+        .await?;
+
         Ok(Self { pool })
     }
 
@@ -13,8 +13,16 @@ pub struct WsMessage {
     pub id: u32,
 }
 
+// a market as published by the engine on the `markets` stream
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Market {
+    pub symbol: String,
+    pub status: String,
+}
+
 impl WsMessage {
-    pub fn parse_subscription(&self) -> Option<(SubscriptionType, SupportedAssetPairs)> {
+    // the market is checked against the registry by the caller
+    pub fn parse_subscription(&self) -> Option<(SubscriptionType, String)> {
         if self.params.is_empty() {
             return None;
         }
@@ -30,9 +38,8 @@ impl WsMessage {
         let asset_pair_str = parts[1];
 
         let subscription_type = SubscriptionType::from_str(subscription_type_str)?;
-        let asset_pair = SupportedAssetPairs::from_str(asset_pair_str).ok()?;
 
-        Some((subscription_type, asset_pair))
+        Some((subscription_type, asset_pair_str.to_string()))
     }
 }
 
@@ -44,6 +51,8 @@ pub enum SubscriptionType {
     trade,
     #[allow(non_camel_case_types)]
     ticker,
+    #[allow(non_camel_case_types)]
+    order,
 }
 
 impl SubscriptionType {
@@ -52,31 +61,8 @@ impl SubscriptionType {
             "depth" => Some(SubscriptionType::depth),
             "trade" => Some(SubscriptionType::trade),
             "ticker" => Some(SubscriptionType::ticker),
+            "order" => Some(SubscriptionType::order),
             _ => None,
         }
     }
 }
-
-#[derive(Debug, Clone)]
-pub enum SupportedAssetPairs {
-    #[allow(non_camel_case_types)]
-    BTC_USDT,
-    #[allow(non_camel_case_types)]
-    ETH_USDT,
-    #[allow(non_camel_case_types)]
-    SOL_USDT,
-    #[allow(non_camel_case_types)]
-    SOL_USDC,
-}
-
-impl SupportedAssetPairs {
-    pub fn from_str(asset_pair_str: &str) -> Result<SupportedAssetPairs, &'static str> {
-        match asset_pair_str {
-            "BTC_USDT" => Ok(SupportedAssetPairs::BTC_USDT),
-            "ETH_USDT" => Ok(SupportedAssetPairs::ETH_USDT),
-            "SOL_USDT" => Ok(SupportedAssetPairs::SOL_USDT),
-            "SOL_USDC" => Ok(SupportedAssetPairs::SOL_USDC),
-            _ => Err("Unsupported asset pair"),
-        }
-    }
-}
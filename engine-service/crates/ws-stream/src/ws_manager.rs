@@ -3,25 +3,61 @@ use redis::RedisManager;
 use tokio_tungstenite::tungstenite::Message;
 
 use crate::{
-    types::{WsMessage, WsResponse},
+    types::{Market, WsMessage, WsResponse},
     user::User,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct WsManager {
     pub users: HashMap<String, User>,
     pub subscriptions: HashMap<String, Vec<String>>, // user_id -> [subscription_id]
     pub reverse_subscriptions: HashMap<String, Vec<String>>, // subscription_id -> [user_id]
+    pub markets: HashSet<String>, // symbols of listed markets, kept in sync with the engine's registry
     pub redis_connection: RedisManager,
 }
 
 impl WsManager {
     pub async fn new() -> Self {
-        Self {
+        let redis_connection = RedisManager::new().await.unwrap();
+
+        // registry changes arrive on the markets stream, the current registry is stored under the same key
+        redis_connection
+            .subscribe("markets")
+            .await
+            .expect("Failed to subscribe to markets in redis");
+
+        let mut ws_manager = Self {
             users: HashMap::new(),
             subscriptions: HashMap::new(),
             reverse_subscriptions: HashMap::new(),
-            redis_connection: RedisManager::new().await.unwrap(),
+            markets: HashSet::new(),
+            redis_connection,
+        };
+
+        match ws_manager.redis_connection.get("markets").await {
+            Ok(Some(markets_message)) => {
+                let ws_message: WsResponse =
+                    serde_json::from_str(markets_message.as_str()).unwrap();
+                ws_manager.update_markets(ws_message.data);
+            }
+            Ok(None) => println!("No markets in redis yet, waiting for the engine"),
+            Err(e) => eprintln!("Failed to get markets from redis: {}", e),
+        }
+
+        ws_manager
+    }
+
+    pub fn update_markets(&mut self, data: serde_json::Value) {
+        match serde_json::from_value::<Vec<Market>>(data) {
+            Ok(markets) => {
+                self.markets = markets
+                    .into_iter()
+                    .filter(|market| market.status != "DELISTED")
+                    .map(|market| market.symbol)
+                    .collect();
+                println!("Markets updated: {:?}", self.markets);
+            }
+            Err(e) => eprintln!("Failed to parse markets: {}", e),
         }
     }
 
@@ -48,7 +84,11 @@ impl WsManager {
                     return;
                 }
             };
-            let subscription_id = format!("{:?}.{:?}", subscription_type, asset_pair);
+            if !self.markets.contains(&asset_pair) {
+                eprintln!("Unsupported market: {:?}", message.params);
+                return;
+            }
+            let subscription_id = format!("{:?}.{}", subscription_type, asset_pair);
 
             if let Some(subscriptions) = self.subscriptions.get_mut(user_id) {
                 subscriptions.push(subscription_id.clone());
@@ -81,7 +121,7 @@ impl WsManager {
                     return;
                 }
             };
-            let subscription_id = format!("{:?}.{:?}", subscription_type, asset_pair);
+            let subscription_id = format!("{:?}.{}", subscription_type, asset_pair);
 
             if let Some(subscriptions) = self.subscriptions.get_mut(user_id) {
                 subscriptions.retain(|id| id != &subscription_id);
@@ -105,6 +145,11 @@ impl WsManager {
     pub async fn send_to_ws_stream(&mut self, message: String) {
         let ws_message: WsResponse = serde_json::from_str(message.as_str()).unwrap();
 
+        if ws_message.stream == "markets" {
+            self.update_markets(ws_message.data);
+            return;
+        }
+
         if let Some(users) = self.reverse_subscriptions.get(ws_message.stream.as_str()) {
             for user_id in users {
                 if let Some(user) = self.users.get_mut(user_id) {
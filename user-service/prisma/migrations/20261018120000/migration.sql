@@ -0,0 +1,7 @@
+-- CreateTable
+CREATE TABLE "processed_settlements" (
+    "key" TEXT NOT NULL,
+    "created_at" TIMESTAMP(3) NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT "processed_settlements_pkey" PRIMARY KEY ("key")
+);
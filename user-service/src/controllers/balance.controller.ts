@@ -1,13 +1,14 @@
 import Send from "../utils/response.utils";
+import AuthMiddleware from "../middlewares/auth.middleware";
 import { prisma } from "../db.js";
+import type { Prisma } from "../generated/prisma/index";
 import type { Request, Response } from "express";
 
 class BalanceController {
     // Helper method to get user ID from either JWT or service header
     private static getUserId(req: Request): number | null {
         // Check for service API key first (for inter-service calls)
-        const serviceKey = req.headers['x-service-key'];
-        if (serviceKey === process.env.SERVICE_API_KEY) {
+        if (AuthMiddleware.isService(req)) {
             const userId = req.headers['x-user-id'];
             return userId ? parseInt(userId as string) : null;
         }
@@ -17,6 +18,110 @@ class BalanceController {
         return jwtUserId ? parseInt(jwtUserId) : null;
     }
 
+    // Settlement legs from the engine carry an idempotency key of the trade or balance change and the leg.
+    // A leg whose key is recorded was applied before and is only acknowledged when the engine retries it
+    private static idempotencyKey(req: Request): string | null {
+        const key = req.headers['x-idempotency-key'];
+        return typeof key === 'string' ? key : null;
+    }
+
+    private static isApplied = async (tx: Prisma.TransactionClient, key: string | null) => {
+        if (!key) {
+            return false;
+        }
+        return (await tx.processed_settlements.findUnique({ where: { key } })) !== null;
+    };
+
+    // Recorded in the same transaction as the balance change, so a leg is never applied without its key
+    private static markApplied = async (tx: Prisma.TransactionClient, key: string | null) => {
+        if (key) {
+            await tx.processed_settlements.create({ data: { key } });
+        }
+    };
+
+    // Deposits and withdrawals go to the engine, which owns the ledger - the change comes back
+    // through /update once the engine has applied it, so nothing is written here
+    private static forwardToEngine = async (req: Request, res: Response, path: "deposit" | "withdraw") => {
+        const userId = BalanceController.getUserId(req);
+        if (!userId) {
+            return Send.unauthorized(res, { message: "Invalid authentication" });
+        }
+
+        const { currency, amount } = req.body;
+        if (!currency || !amount) {
+            return Send.badRequest(res, {}, "Currency and amount are required");
+        }
+        if (parseFloat(amount) <= 0) {
+            return Send.badRequest(res, {}, "Amount must be positive");
+        }
+
+        const routerUrl = process.env.ROUTER_URL || "http://localhost:8080";
+        const response = await fetch(`${routerUrl}/api/v1/admin/balances/${path}`, {
+            method: "POST",
+            headers: {
+                "Content-Type": "application/json",
+                "x-admin-key": process.env.ADMIN_API_KEY as string,
+            },
+            body: JSON.stringify({
+                user_id: userId.toString(),
+                asset: currency,
+                amount: amount.toString(),
+            }),
+        });
+        if (!response.ok) {
+            console.error(`Engine rejected ${path}:`, response.status);
+            return Send.error(res, {}, `Failed to ${path}`);
+        }
+
+        return Send.success(res, await response.json());
+    };
+
+    // Credit a confirmed deposit
+    static deposit = async (req: Request, res: Response) => {
+        try {
+            return await BalanceController.forwardToEngine(req, res, "deposit");
+        } catch (error) {
+            console.error("Error depositing:", error);
+            return Send.error(res, {}, "Internal server error");
+        }
+    };
+
+    // Debit a withdrawal before it is sent out
+    static withdraw = async (req: Request, res: Response) => {
+        try {
+            return await BalanceController.forwardToEngine(req, res, "withdraw");
+        } catch (error) {
+            console.error("Error withdrawing:", error);
+            return Send.error(res, {}, "Internal server error");
+        }
+    };
+
+    // Every user's balances - the engine imports them the first time it starts with an empty ledger
+    static exportBalances = async (req: Request, res: Response) => {
+        try {
+            const balances = await prisma.user_balances.findMany({
+                select: {
+                    user_id: true,
+                    currency: true,
+                    balance: true,
+                    locked_balance: true,
+                }
+            });
+
+            return Send.success(res, {
+                balances: balances.map(balance => ({
+                    user_id: balance.user_id.toString(),
+                    currency: balance.currency,
+                    balance: balance.balance.toString(),
+                    locked_balance: balance.locked_balance.toString(),
+                }))
+            });
+        } catch (error) {
+            console.error("Error exporting balances:", error);
+            return Send.error(res, {}, "Internal server error");
+        }
+    };
+
     // Get user balances for all currencies
     static getBalances = async (req: Request, res: Response) => {
         try {
@@ -117,40 +222,57 @@ class BalanceController {
                 return Send.badRequest(res, {}, "Amount must be positive");
             }
 
-            // Check if user has sufficient balance
-            const balance = await prisma.user_balances.findUnique({
-                where: {
-                    user_id_currency: {
-                        user_id: userId,
-                        currency: currency as any
+            const key = BalanceController.idempotencyKey(req);
+            const result = await prisma.$transaction(async (tx) => {
+                if (await BalanceController.isApplied(tx, key)) {
+                    return "applied";
+                }
+
+                // Check if user has sufficient balance
+                const balance = await tx.user_balances.findUnique({
+                    where: {
+                        user_id_currency: {
+                            user_id: userId,
+                            currency: currency as any
+                        }
                     }
+                });
+
+                if (!balance) {
+                    return "not_found";
                 }
+
+                const availableBalance = parseFloat(balance.balance.toString()) - parseFloat(balance.locked_balance.toString());
+                if (availableBalance < lockAmount) {
+                    return "insufficient";
+                }
+
+                // Lock the funds
+                await BalanceController.markApplied(tx, key);
+                await tx.user_balances.update({
+                    where: {
+                        user_id_currency: {
+                            user_id: userId,
+                            currency: currency as any
+                        }
+                    },
+                    data: {
+                        locked_balance: {
+                            increment: lockAmount
+                        }
+                    }
+                });
+
+                return "locked";
             });
 
-            if (!balance) {
+            if (result === "not_found") {
                 return Send.notFound(res, {}, "Balance not found");
             }
-
-            const availableBalance = parseFloat(balance.balance.toString()) - parseFloat(balance.locked_balance.toString());
-            if (availableBalance < lockAmount) {
+            if (result === "insufficient") {
                 return Send.badRequest(res, {}, "Insufficient funds");
             }
 
-            // Lock the funds
-            await prisma.user_balances.update({
-                where: {
-                    user_id_currency: {
-                        user_id: userId,
-                        currency: currency as any
-                    }
-                },
-                data: {
-                    locked_balance: {
-                        increment: lockAmount
-                    }
-                }
-            });
-
             return Send.success(res, { message: "Funds locked successfully" });
         } catch (error) {
             console.error("Error locking funds:", error);
@@ -177,40 +299,57 @@ class BalanceController {
                 return Send.badRequest(res, {}, "Amount must be positive");
             }
 
-            // Check if user has sufficient locked balance
-            const balance = await prisma.user_balances.findUnique({
-                where: {
-                    user_id_currency: {
-                        user_id: userId,
-                        currency: currency as any
+            const key = BalanceController.idempotencyKey(req);
+            const result = await prisma.$transaction(async (tx) => {
+                if (await BalanceController.isApplied(tx, key)) {
+                    return "applied";
+                }
+
+                // Check if user has sufficient locked balance
+                const balance = await tx.user_balances.findUnique({
+                    where: {
+                        user_id_currency: {
+                            user_id: userId,
+                            currency: currency as any
+                        }
                     }
+                });
+
+                if (!balance) {
+                    return "not_found";
                 }
+
+                const lockedBalance = parseFloat(balance.locked_balance.toString());
+                if (lockedBalance < unlockAmount) {
+                    return "insufficient";
+                }
+
+                // Unlock the funds
+                await BalanceController.markApplied(tx, key);
+                await tx.user_balances.update({
+                    where: {
+                        user_id_currency: {
+                            user_id: userId,
+                            currency: currency as any
+                        }
+                    },
+                    data: {
+                        locked_balance: {
+                            decrement: unlockAmount
+                        }
+                    }
+                });
+
+                return "unlocked";
             });
 
-            if (!balance) {
+            if (result === "not_found") {
                 return Send.notFound(res, {}, "Balance not found");
             }
-
-            const lockedBalance = parseFloat(balance.locked_balance.toString());
-            if (lockedBalance < unlockAmount) {
+            if (result === "insufficient") {
                 return Send.badRequest(res, {}, "Insufficient locked funds");
             }
 
-            // Unlock the funds
-            await prisma.user_balances.update({
-                where: {
-                    user_id_currency: {
-                        user_id: userId,
-                        currency: currency as any
-                    }
-                },
-                data: {
-                    locked_balance: {
-                        decrement: unlockAmount
-                    }
-                }
-            });
-
             return Send.success(res, { message: "Funds unlocked successfully" });
         } catch (error) {
             console.error("Error unlocking funds:", error);
@@ -245,20 +384,28 @@ class BalanceController {
                 ? { balance: { increment: updateAmount } }
                 : { balance: { decrement: updateAmount } };
 
-            await prisma.user_balances.upsert({
-                where: {
-                    user_id_currency: {
+            const key = BalanceController.idempotencyKey(req);
+            await prisma.$transaction(async (tx) => {
+                if (await BalanceController.isApplied(tx, key)) {
+                    return;
+                }
+
+                await BalanceController.markApplied(tx, key);
+                await tx.user_balances.upsert({
+                    where: {
+                        user_id_currency: {
+                            user_id: userId,
+                            currency: currency as any
+                        }
+                    },
+                    update: updateData,
+                    create: {
                         user_id: userId,
-                        currency: currency as any
+                        currency: currency as any,
+                        balance: operation === 'add' ? updateAmount : -updateAmount,
+                        locked_balance: 0
                     }
-                },
-                update: updateData,
-                create: {
-                    user_id: userId,
-                    currency: currency as any,
-                    balance: operation === 'add' ? updateAmount : -updateAmount,
-                    locked_balance: 0
-                }
+                });
             });
 
             return Send.success(res, { message: `Balance ${operation}ed successfully` });
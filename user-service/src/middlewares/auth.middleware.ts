@@ -8,6 +8,25 @@ export interface DecodedToken {
 }
 
 class AuthMiddleware {
+    // Inter-service calls carry the key from SERVICE_API_KEY in the x-service-key header. Without a
+    // configured key no request counts as a service, so a missing header can never match a missing key
+    static isService = (req: Request): boolean => {
+        const serviceKey = process.env.SERVICE_API_KEY;
+        if (!serviceKey) {
+            return false;
+        }
+
+        return req.headers['x-service-key'] === serviceKey;
+    };
+
+    static authenticateService = (req: Request, res: Response, next: NextFunction) => {
+        if (!AuthMiddleware.isService(req)) {
+            return Send.unauthorized(res, { message: "Invalid service key" });
+        }
+
+        next();
+    };
+
     static authenticateUser = (req: Request, res: Response, next: NextFunction) => {
         const token = req.cookies.accessToken;
 
@@ -6,8 +6,7 @@ import type { Request, Response, NextFunction } from "express";
 // Middleware that allows either JWT or service authentication
 const authenticateUserOrService = (req: Request, res: Response, next: NextFunction) => {
     // Check for service API key first
-    const serviceKey = req.headers['x-service-key'];
-    if (serviceKey === process.env.SERVICE_API_KEY) {
+    if (AuthMiddleware.isService(req)) {
         const userId = req.headers['x-user-id'];
         if (userId) {
             (req as any).userId = parseInt(userId as string);
@@ -30,6 +29,30 @@ class BalanceRouter extends BaseRouter {
                 ],
                 handler: BalanceController.getBalances
             },
+            {
+                method: "get",
+                path: "/export",
+                middlewares: [
+                    AuthMiddleware.authenticateService,
+                ],
+                handler: BalanceController.exportBalances
+            },
+            {
+                method: "post",
+                path: "/deposit",
+                middlewares: [
+                    AuthMiddleware.authenticateService,
+                ],
+                handler: BalanceController.deposit
+            },
+            {
+                method: "post",
+                path: "/withdraw",
+                middlewares: [
+                    AuthMiddleware.authenticateService,
+                ],
+                handler: BalanceController.withdraw
+            },
             {
                 method: "get",
                 path: "/:currency",